@@ -1,16 +1,40 @@
 from __future__ import annotations
 
-import asyncio
 import sys
-from functools import partial
-from typing import TYPE_CHECKING, Literal, TypedDict
+from collections.abc import Mapping
+from typing import IO, TYPE_CHECKING, Iterable, Literal, TypedDict
 
 if sys.version_info <= (3, 11):
     from typing_extensions import Unpack
 else:
     from typing import Unpack
 
-from .primp import RClient  # type: ignore
+from .primp import (  # type: ignore
+    Article,
+    ConnectError,
+    Cookie,
+    DecodeError,
+    Element,
+    Headers,
+    HTTPStatusError,
+    InvalidURL,
+    Link,
+    Metadata,
+    ProxyError,
+    RClient,
+    RedirectResponse,
+    RequestError,
+    ResponseTooLarge,
+    Retry,
+    ServerSentEvent,
+    TimeoutError,
+    Timings,
+    TLSError,
+    TooManyRedirects,
+)
+
+# Headers is a read-only, case-insensitive mapping
+Mapping.register(Headers)
 
 if TYPE_CHECKING:
     HttpMethod = Literal["GET", "HEAD", "OPTIONS", "DELETE", "POST", "PUT", "PATCH"]
@@ -31,6 +55,7 @@ if TYPE_CHECKING:
         "firefox_109", "firefox_117", "firefox_128", "firefox_133",
     ]  # fmt: skip
     IMPERSONATE_OS = Literal["android", "ios", "linux", "macos", "windows"]
+    RUNTIME = Literal["current_thread", "multi_thread", "dedicated"]
 
     class RequestParams(TypedDict, total=False):
         auth: tuple[str, str | None] | None
@@ -39,10 +64,15 @@ if TYPE_CHECKING:
         headers: dict[str, str] | None
         cookies: dict[str, str] | None
         timeout: float | None
-        content: bytes | None
+        content: bytes | Iterable[bytes] | IO[bytes] | None
         data: dict[str, str] | None
         json: dict[str, str] | None
         files: dict[str, str] | None
+        stream: bool | None
+        retries: int | Retry | None
+        decompress: bool | None
+        max_content_length: int | None
+        max_decompressed_size: int | None
 
     class ClientRequestParams(RequestParams):
         impersonate: IMPERSONATE | None
@@ -83,6 +113,16 @@ class Client(RClient):
         ca_cert_file: str | None = None,
         https_only: bool | None = False,
         http2_only: bool | None = False,
+        runtime: RUNTIME | None = None,
+        worker_threads: int | None = None,
+        raise_for_status: bool = False,
+        retries: int | Retry | None = None,
+        rate_limit: float | None = None,
+        max_connections_per_host: int | None = None,
+        decompress: bool = True,
+        accept_encoding: list[Literal["gzip", "br", "zstd", "deflate"]] | None = None,
+        max_content_length: int | None = None,
+        max_decompressed_size: int | None = None,
     ):
         """
         Args:
@@ -119,6 +159,30 @@ class Client(RClient):
             ca_cert_file: path to CA certificate store. Default is None.
             https_only: restrict the Client to be used with HTTPS only requests. Default is False.
             http2_only: if true - use only HTTP/2, if false - use only HTTP/1. Default is False.
+            runtime: Tokio runtime to execute requests on: "current_thread" (global single-threaded runtime),
+                "multi_thread" (global multi-threaded runtime) or "dedicated" (multi-threaded runtime owned
+                by this client). Default is the env var PRIMP_RUNTIME or "current_thread".
+            worker_threads: number of worker threads for the "multi_thread" and "dedicated" runtimes.
+                Default is the env var PRIMP_WORKER_THREADS or the number of CPUs.
+            raise_for_status: raise `HTTPStatusError` for responses with a 4xx or 5xx status code. Default is False.
+            retries: the number of retries of failed requests, or a `Retry` policy. Default is None (no retries).
+            rate_limit: maximum number of requests per second per host. Requests over the limit wait
+                for their turn. Default is None (no limit).
+            max_connections_per_host: maximum number of requests in flight per host. A streamed response
+                keeps its slot until it is read or closed. Default is None (no limit).
+            decompress: decompress the response bodies according to their `Content-Encoding`. The `Content-Encoding`
+                and `Content-Length` headers of a decompressed response are removed, as they describe the compressed
+                body. If False, the raw bodies are returned, with their `Content-Encoding` and `Content-Length` headers.
+                Can be overridden per request. Default is True.
+            accept_encoding: the encodings advertised in the `Accept-Encoding` header, among "gzip", "br", "zstd"
+                and "deflate", overriding the impersonated header. An empty list sends "identity".
+                Default is None (the impersonated header, or all of them).
+            max_content_length: maximum size of the response bodies as received, in bytes. Reading a larger body
+                raises `ResponseTooLarge`, before it is read if its `Content-Length` is larger. Can be overridden
+                per request. Default is None (no limit).
+            max_decompressed_size: maximum size of the decompressed response bodies, in bytes. Decompressing
+                a larger body raises `ResponseTooLarge`, protecting against decompression bombs. Can be
+                overridden per request. Default is None (no limit).
         """
         super().__init__()
 
@@ -152,6 +216,17 @@ class Client(RClient):
     def patch(self, url: str, **kwargs: Unpack[RequestParams]):
         return self.request(method="PATCH", url=url, **kwargs)
 
+    def map(
+        self,
+        method: HttpMethod,
+        urls: list[str],
+        concurrency: int | None = None,
+        **kwargs: Unpack[RequestParams],
+    ):
+        """Sends the same request to each of `urls` concurrently, see `request_many`."""
+        requests = [{"method": method, "url": url, **kwargs} for url in urls]
+        return self.request_many(requests, concurrency=concurrency)
+
 
 class AsyncClient(Client):
     def __init__(self, *args, **kwargs):
@@ -163,12 +238,8 @@ class AsyncClient(Client):
     async def __aexit__(self, *args):
         del self
 
-    async def _run_sync_asyncio(self, fn, *args, **kwargs):
-        loop = asyncio.get_running_loop()
-        return await loop.run_in_executor(None, partial(fn, *args, **kwargs))
-
     async def request(self, method: HttpMethod, url: str, **kwargs: Unpack[RequestParams]):
-        return await self._run_sync_asyncio(super().request, method=method, url=url, **kwargs)
+        return await self.arequest(method=method, url=url, **kwargs)
 
     async def get(self, url: str, **kwargs: Unpack[RequestParams]):
         return await self.request(method="GET", url=url, **kwargs)
@@ -191,6 +262,22 @@ class AsyncClient(Client):
     async def patch(self, url: str, **kwargs: Unpack[RequestParams]):
         return await self.request(method="PATCH", url=url, **kwargs)
 
+    async def request_many(self, requests: list[dict], concurrency: int | None = None):
+        return await self.arequest_many(requests, concurrency=concurrency)
+
+    async def map(
+        self,
+        method: HttpMethod,
+        urls: list[str],
+        concurrency: int | None = None,
+        **kwargs: Unpack[RequestParams],
+    ):
+        requests = [{"method": method, "url": url, **kwargs} for url in urls]
+        return await self.request_many(requests, concurrency=concurrency)
+
+    async def download(self, url: str, path: str, **kwargs):
+        return await self.adownload(url=url, path=path, **kwargs)
+
 
 def request(
     method: HttpMethod,
@@ -230,7 +317,8 @@ def request(
         headers: an optional map of HTTP headers to send with requests. If `impersonate` is set, this will be ignored.
         cookies: an optional map of cookies to send with requests as the `Cookie` header.
         timeout: the timeout for the request in seconds. Default is 30.
-        content: he content to send in the request body as bytes. Default is None.
+        content: the content to send in the request body: bytes, or an iterator of bytes or a binary
+            file-like object to stream with chunked transfer encoding. Default is None.
         data: the form data to send in the request body. Default is None.
         json: a JSON serializable object to send in the request body. Default is None.
         files: a map of file fields to file paths to be sent as multipart/form-data. Default is None.
@@ -0,0 +1,148 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use anyhow::{anyhow, Result};
+use bytes::{Buf, Bytes};
+use pyo3::prelude::*;
+use pyo3::pybacked::PyBackedBytes;
+use pyo3::types::{PyByteArray, PyBytes, PyIterator, PyString};
+use rquest::Body;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::sync::mpsc;
+use tokio_util::codec::{BytesCodec, FramedRead};
+
+// Size of the chunks read from file-like objects
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Python object the body of a streamed upload is pulled from.
+enum PySource {
+    /// Iterator or generator yielding bytes.
+    Iterator(Py<PyIterator>),
+    /// Binary file-like object with a `read(size)` method.
+    File(Py<PyAny>),
+}
+
+impl PySource {
+    /// Fetches the next non-empty chunk, or None at the end of the body.
+    fn next_chunk(&self, py: Python) -> PyResult<Option<Bytes>> {
+        match self {
+            PySource::Iterator(iter) => {
+                let mut iter = iter.bind(py).clone();
+                for item in &mut iter {
+                    let chunk = chunk_to_bytes(&item?)?;
+                    if !chunk.is_empty() {
+                        return Ok(Some(chunk));
+                    }
+                }
+                Ok(None)
+            }
+            PySource::File(file) => {
+                let data = file.bind(py).call_method1("read", (FILE_CHUNK_SIZE,))?;
+                let chunk = chunk_to_bytes(&data)?;
+                Ok((!chunk.is_empty()).then_some(chunk))
+            }
+        }
+    }
+}
+
+/// Sends the chunks of `source` to `tx` until the end of the body, an error, or the request
+/// being dropped. Runs on a blocking thread: it waits for the GIL and runs Python code.
+fn pull_chunks(source: PySource, tx: mpsc::Sender<PyResult<Bytes>>) {
+    loop {
+        let Some(item) = Python::with_gil(|py| source.next_chunk(py)).transpose() else {
+            break;
+        };
+        let failed = item.is_err();
+        if tx.blocking_send(item).is_err() || failed {
+            break;
+        }
+    }
+    Python::with_gil(|_| drop(source));
+}
+
+/// `AsyncRead` over a Python iterator or file-like object.
+///
+/// The chunks are pulled by a blocking task started on the first read, so that runtime workers
+/// never wait for the GIL. The request must be driven with the GIL released, as `request` and
+/// `arequest` do.
+struct PyReader {
+    // Taken when the blocking task starts
+    source: Option<PySource>,
+    chunks: Option<mpsc::Receiver<PyResult<Bytes>>>,
+    pending: Bytes,
+}
+
+impl AsyncRead for PyReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.pending.is_empty() {
+            if let Some(source) = this.source.take() {
+                // One chunk ahead at most
+                let (tx, rx) = mpsc::channel(1);
+                tokio::task::spawn_blocking(move || pull_chunks(source, tx));
+                this.chunks = Some(rx);
+            }
+            let Some(chunks) = &mut this.chunks else {
+                return Poll::Ready(Ok(()));
+            };
+            match ready!(chunks.poll_recv(cx)) {
+                Some(Ok(chunk)) => this.pending = chunk,
+                Some(Err(err)) => return Poll::Ready(Err(io::Error::other(err))),
+                None => {
+                    this.chunks = None;
+                    return Poll::Ready(Ok(()));
+                }
+            }
+        }
+        let n = buf.remaining().min(this.pending.len());
+        buf.put_slice(&this.pending[..n]);
+        this.pending.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Converts a chunk of a streamed upload: bytes or bytearray.
+fn chunk_to_bytes(chunk: &Bound<'_, PyAny>) -> PyResult<Bytes> {
+    let data: PyBackedBytes = chunk.extract()?;
+    Ok(Bytes::copy_from_slice(&data))
+}
+
+/// Converts the `content` argument into a request body.
+///
+/// Bytes are sent as is. Iterators, generators and binary file-like objects are streamed
+/// with chunked transfer encoding, the same way multipart file uploads are.
+pub fn content_to_body(content: &Bound<'_, PyAny>) -> Result<Body> {
+    if let Ok(bytes) = content.downcast::<PyBytes>() {
+        return Ok(Body::from(bytes.as_bytes().to_vec()));
+    }
+    if let Ok(bytearray) = content.downcast::<PyByteArray>() {
+        return Ok(Body::from(bytearray.to_vec()));
+    }
+    if content.is_instance_of::<PyString>() {
+        return Err(anyhow!(
+            "content must be bytes, an iterator of bytes or a binary file-like object, not str"
+        ));
+    }
+    let source = if content.hasattr("read")? {
+        PySource::File(content.clone().unbind())
+    } else if let Ok(iter) = content.try_iter() {
+        PySource::Iterator(iter.unbind())
+    } else {
+        return Err(anyhow!(
+            "content must be bytes, an iterator of bytes or a binary file-like object, not {}",
+            content.get_type().name()?
+        ));
+    };
+    let reader = PyReader {
+        source: Some(source),
+        chunks: None,
+        pending: Bytes::new(),
+    };
+    let stream = FramedRead::new(reader, BytesCodec::new());
+    Ok(Body::wrap_stream(stream))
+}
@@ -0,0 +1,273 @@
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::prelude::*;
+use rquest::cookie::{CookieStore, Jar};
+use rquest::header::{HeaderMap, HeaderValue, SET_COOKIE};
+use rquest::Url;
+
+/// A cookie set by a `Set-Cookie` header, with its attributes.
+///
+/// Attributes missing from the header are None (or False for the flags); `domain` and `path`
+/// are reported as sent by the server, not as defaulted by a cookie store.
+#[pyclass(frozen)]
+#[derive(Clone, Debug)]
+pub struct Cookie {
+    #[pyo3(get)]
+    pub name: String,
+    #[pyo3(get)]
+    pub value: String,
+    #[pyo3(get)]
+    pub domain: Option<String>,
+    #[pyo3(get)]
+    pub path: Option<String>,
+    /// Expiration date, as a Unix timestamp in seconds.
+    #[pyo3(get)]
+    pub expires: Option<i64>,
+    /// Lifetime in seconds, taking precedence over `expires`.
+    #[pyo3(get)]
+    pub max_age: Option<i64>,
+    #[pyo3(get)]
+    pub secure: bool,
+    #[pyo3(get)]
+    pub httponly: bool,
+    /// "Strict", "Lax" or "None".
+    #[pyo3(get)]
+    pub samesite: Option<String>,
+    #[pyo3(get)]
+    pub partitioned: bool,
+}
+
+impl Cookie {
+    /// Parses a `Set-Cookie` header value. Returns None if it is not a valid cookie.
+    pub fn parse(value: &HeaderValue) -> Option<Self> {
+        let value = std::str::from_utf8(value.as_bytes()).ok()?;
+        let cookie = cookie::Cookie::parse(value).ok()?;
+        Some(Cookie {
+            name: cookie.name().to_string(),
+            value: cookie.value().to_string(),
+            domain: cookie.domain().map(str::to_string),
+            path: cookie.path().map(str::to_string),
+            expires: cookie.expires_datetime().map(|dt| dt.unix_timestamp()),
+            max_age: cookie.max_age().map(|max_age| max_age.whole_seconds()),
+            secure: cookie.secure().unwrap_or(false),
+            httponly: cookie.http_only().unwrap_or(false),
+            samesite: cookie.same_site().map(|same_site| same_site.to_string()),
+            partitioned: cookie.partitioned().unwrap_or(false),
+        })
+    }
+
+    /// The cookies of the `Set-Cookie` headers, in order, skipping the invalid ones.
+    pub fn from_headers(headers: &HeaderMap) -> Vec<Self> {
+        headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(Cookie::parse)
+            .collect()
+    }
+}
+
+#[pymethods]
+impl Cookie {
+    fn __repr__(&self) -> String {
+        let mut repr = format!("Cookie({:?}={:?}", self.name, self.value);
+        if let Some(domain) = &self.domain {
+            repr.push_str(&format!(", domain={:?}", domain));
+        }
+        if let Some(path) = &self.path {
+            repr.push_str(&format!(", path={:?}", path));
+        }
+        if let Some(expires) = self.expires {
+            repr.push_str(&format!(", expires={}", expires));
+        }
+        if let Some(max_age) = self.max_age {
+            repr.push_str(&format!(", max_age={}", max_age));
+        }
+        for (flag, set) in [
+            ("secure", self.secure),
+            ("httponly", self.httponly),
+            ("partitioned", self.partitioned),
+        ] {
+            if set {
+                repr.push_str(&format!(", {}=True", flag));
+            }
+        }
+        if let Some(samesite) = &self.samesite {
+            repr.push_str(&format!(", samesite={:?}", samesite));
+        }
+        repr.push(')');
+        repr
+    }
+}
+
+/// Cookie store of a client, keeping the attributes of the cookies next to `rquest`'s `Jar`.
+///
+/// The jar decides which cookies are sent; the records only describe them.
+#[derive(Default)]
+pub struct CookieJar {
+    jar: Jar,
+    /// The received cookies, the latest of each one last. Like the jar, it drops the cookies
+    /// once they expire.
+    records: Mutex<Vec<Record>>,
+}
+
+/// A received cookie with the host that set it.
+struct Record {
+    origin: String,
+    cookie: Cookie,
+    /// Expiration date as a Unix timestamp, from `Max-Age` or `Expires`. None for session cookies.
+    expires_at: Option<i64>,
+}
+
+impl Record {
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// The current time as a Unix timestamp in seconds.
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+impl CookieJar {
+    /// The cookies the client sends to `url`, with their attributes.
+    pub fn cookies_for(&self, url: &Url) -> Vec<Cookie> {
+        let Some(sent) = self.jar.cookies(url) else {
+            return Vec::new();
+        };
+        let sent = String::from_utf8_lossy(sent.as_bytes());
+        let host = url.host_str().unwrap_or_default();
+        let mut records = self.records.lock().unwrap();
+        let now = unix_now();
+        records.retain(|record| !record.is_expired(now));
+        sent.split(';')
+            .filter_map(|pair| pair.split_once('='))
+            .filter_map(|(name, value)| {
+                let (name, value) = (name.trim(), value.trim());
+                records
+                    .iter()
+                    .rev()
+                    .find(|Record { origin, cookie, .. }| {
+                        cookie.name == name && cookie.value == value && cookie.matches(origin, host)
+                    })
+                    .map(|record| record.cookie.clone())
+            })
+            .collect()
+    }
+}
+
+impl CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let headers: Vec<&HeaderValue> = cookie_headers.collect();
+        let origin = url.host_str().unwrap_or_default().to_string();
+        {
+            let mut records = self.records.lock().unwrap();
+            let now = unix_now();
+            records.retain(|record| !record.is_expired(now));
+            for cookie in headers.iter().copied().filter_map(Cookie::parse) {
+                // A cookie replaces the one with the same name, domain and path
+                records.retain(|record| {
+                    let c = &record.cookie;
+                    !(c.name == cookie.name
+                        && c.domain == cookie.domain
+                        && c.path == cookie.path
+                        && (c.domain.is_some() || record.origin == origin))
+                });
+                let record = Record {
+                    origin: origin.clone(),
+                    expires_at: cookie.expires_at(now),
+                    cookie,
+                };
+                // An expired cookie, e.g. `Max-Age=0`, deletes the previous one only
+                if !record.is_expired(now) {
+                    records.push(record);
+                }
+            }
+        }
+        self.jar.set_cookies(&mut headers.into_iter(), url);
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        self.jar.cookies(url)
+    }
+}
+
+impl Cookie {
+    /// Expiration date of the cookie received at `now`, as a Unix timestamp. `Max-Age` takes
+    /// precedence over `Expires`.
+    fn expires_at(&self, now: i64) -> Option<i64> {
+        match self.max_age {
+            Some(max_age) => Some(now.saturating_add(max_age)),
+            None => self.expires,
+        }
+    }
+
+    /// Whether the cookie, set by `origin`, applies to `host`.
+    fn matches(&self, origin: &str, host: &str) -> bool {
+        match &self.domain {
+            Some(domain) => {
+                let domain = domain.trim_start_matches('.');
+                host.eq_ignore_ascii_case(domain)
+                    || host
+                        .to_ascii_lowercase()
+                        .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+            }
+            None => host.eq_ignore_ascii_case(origin),
+        }
+    }
+}
+
+#[cfg(test)]
+mod cookies_tests {
+    use super::*;
+
+    #[test]
+    fn test_cookie_matches() {
+        let parse = |value| Cookie::parse(&HeaderValue::from_static(value)).unwrap();
+        let host_only = parse("a=1; Path=/");
+        assert!(host_only.matches("example.com", "example.com"));
+        assert!(!host_only.matches("example.com", "www.example.com"));
+
+        let domain = parse("b=2; Domain=.Example.com");
+        assert!(domain.matches("www.example.com", "example.com"));
+        assert!(domain.matches("www.example.com", "api.example.com"));
+        assert!(!domain.matches("www.example.com", "badexample.com"));
+    }
+
+    #[test]
+    fn test_expired_cookies_are_dropped() {
+        let jar = CookieJar::default();
+        let url = Url::parse("https://example.com/").unwrap();
+        let set = |value: &'static str| {
+            let header = HeaderValue::from_static(value);
+            jar.set_cookies(&mut std::iter::once(&header), &url);
+        };
+        let names = |jar: &CookieJar| {
+            let records = jar.records.lock().unwrap();
+            records
+                .iter()
+                .map(|record| record.cookie.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        set("a=1");
+        set("b=2; Max-Age=3600");
+        set("c=3; Expires=Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(names(&jar), ["a", "b"]);
+
+        // Max-Age=0 deletes the cookie, from the records too
+        set("a=1; Max-Age=0");
+        set("b=2; Max-Age=-1");
+        assert!(names(&jar).is_empty());
+        assert!(jar.cookies_for(&url).is_empty());
+
+        for _ in 0..100 {
+            set("d=4; Max-Age=3600");
+            set("d=4; Max-Age=0");
+        }
+        assert!(names(&jar).is_empty());
+    }
+}
@@ -0,0 +1,435 @@
+use anyhow::{anyhow, Result};
+use cssparser::ParserInput;
+use html5ever::{namespace_url, ns, Namespace};
+use scraper::error::SelectorErrorKind;
+use scraper::selector::{CssLocalName, CssString, NonTSPseudoClass, Parser, PseudoElement, Simple};
+use selectors::attr::{AttrSelectorOperation, CaseSensitivity, NamespaceConstraint};
+use selectors::bloom::BloomFilter;
+use selectors::matching::{
+    self, ElementSelectorFlags, MatchingContext, MatchingForInvalidation, MatchingMode,
+    NeedsSelectorFlags, QuirksMode, SelectorCaches,
+};
+use selectors::parser::{self, ParseRelative};
+use selectors::{Element, OpaqueElement};
+
+use crate::html::{Document, NodeData};
+
+/// A list of CSS selectors separated by commas, matching the elements matched by any of them.
+///
+/// Parsed and matched by `selectors`, the engine of Servo, with the selector syntax of `scraper`:
+/// CSS Selectors Level 3, and `:is()`, `:where()` and `:has()` from Level 4. The pseudo-classes
+/// depending on the state of an element, like `:hover`, and the pseudo-elements are not
+/// supported.
+#[derive(Debug)]
+pub struct SelectorList(parser::SelectorList<Simple>);
+
+impl SelectorList {
+    pub fn parse(css: &str) -> Result<Self> {
+        let mut input = ParserInput::new(css);
+        let mut parser = cssparser::Parser::new(&mut input);
+        parser::SelectorList::parse(&Parser, &mut parser, ParseRelative::No)
+            .map(SelectorList)
+            .map_err(|err| {
+                let position = err.location.column - 1;
+                let kind = SelectorErrorKind::from(err);
+                anyhow!("Invalid CSS selector {css:?}: {kind} at position {position}")
+            })
+    }
+
+    /// The descendants of `scope` matching the selectors, in document order.
+    ///
+    /// `:scope` matches `scope` if it is an element, the root element otherwise.
+    pub fn select<'a>(
+        &'a self,
+        doc: &'a Document,
+        scope: usize,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let scope_element = doc
+            .is_element(scope)
+            .then(|| HtmlElement { doc, id: scope }.opaque());
+        // Caches the positions of the elements among their siblings, for `:nth-*`
+        let mut caches = SelectorCaches::default();
+        doc.descendants(scope).filter(move |&id| {
+            if !doc.is_element(id) {
+                return false;
+            }
+            let mut context = MatchingContext::new(
+                MatchingMode::Normal,
+                None,
+                &mut caches,
+                QuirksMode::NoQuirks,
+                NeedsSelectorFlags::No,
+                MatchingForInvalidation::No,
+            );
+            context.scope_element = scope_element;
+            let element = HtmlElement { doc, id };
+            self.0.slice().iter().any(|selector| {
+                matching::matches_selector(selector, 0, None, &element, &mut context)
+            })
+        })
+    }
+}
+
+/// An element of a document, as matched by `selectors`.
+#[derive(Clone, Copy, Debug)]
+struct HtmlElement<'a> {
+    doc: &'a Document,
+    id: usize,
+}
+
+impl<'a> HtmlElement<'a> {
+    fn element(&self, id: usize) -> Option<Self> {
+        self.doc
+            .is_element(id)
+            .then_some(HtmlElement { doc: self.doc, id })
+    }
+
+    fn name(&self) -> &'a str {
+        self.doc.name(self.id).unwrap_or_default()
+    }
+
+    /// The siblings of the element before it, and after it.
+    fn siblings(&self) -> (&'a [usize], &'a [usize]) {
+        let siblings = self.doc.siblings(self.id);
+        let index = self.doc.node(self.id).index;
+        (&siblings[..index], &siblings[index + 1..])
+    }
+}
+
+impl Element for HtmlElement<'_> {
+    type Impl = Simple;
+
+    fn opaque(&self) -> OpaqueElement {
+        OpaqueElement::new(self.doc.node(self.id))
+    }
+
+    fn parent_element(&self) -> Option<Self> {
+        self.doc
+            .node(self.id)
+            .parent
+            .and_then(|id| self.element(id))
+    }
+
+    fn parent_node_is_shadow_root(&self) -> bool {
+        false
+    }
+
+    fn containing_shadow_host(&self) -> Option<Self> {
+        None
+    }
+
+    fn is_pseudo_element(&self) -> bool {
+        false
+    }
+
+    fn prev_sibling_element(&self) -> Option<Self> {
+        let (before, _) = self.siblings();
+        before.iter().rev().find_map(|&id| self.element(id))
+    }
+
+    fn next_sibling_element(&self) -> Option<Self> {
+        let (_, after) = self.siblings();
+        after.iter().find_map(|&id| self.element(id))
+    }
+
+    fn first_element_child(&self) -> Option<Self> {
+        let child = self.doc.element_children(self.id).next();
+        child.and_then(|id| self.element(id))
+    }
+
+    // Names are matched case-insensitively, and the names of the document are lowercase
+    fn is_html_element_in_html_document(&self) -> bool {
+        true
+    }
+
+    fn has_local_name(&self, local_name: &CssLocalName) -> bool {
+        self.name() == &*local_name.0
+    }
+
+    fn has_namespace(&self, ns: &Namespace) -> bool {
+        *ns == ns!(html)
+    }
+
+    fn is_same_type(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+
+    fn attr_matches(
+        &self,
+        ns: &NamespaceConstraint<&Namespace>,
+        local_name: &CssLocalName,
+        operation: &AttrSelectorOperation<&CssString>,
+    ) -> bool {
+        // The attributes of the document have no namespace
+        if matches!(*ns, NamespaceConstraint::Specific(url) if *url != ns!()) {
+            return false;
+        }
+        self.doc
+            .attrs(self.id)
+            .iter()
+            .any(|(name, value)| *name == *local_name.0 && operation.eval_str(value))
+    }
+
+    fn match_non_ts_pseudo_class(
+        &self,
+        _pc: &NonTSPseudoClass,
+        _context: &mut MatchingContext<'_, Simple>,
+    ) -> bool {
+        false
+    }
+
+    fn match_pseudo_element(
+        &self,
+        _pe: &PseudoElement,
+        _context: &mut MatchingContext<'_, Simple>,
+    ) -> bool {
+        false
+    }
+
+    fn apply_selector_flags(&self, _flags: ElementSelectorFlags) {}
+
+    fn is_link(&self) -> bool {
+        matches!(self.name(), "a" | "area" | "link") && self.doc.attr(self.id, "href").is_some()
+    }
+
+    fn is_html_slot_element(&self) -> bool {
+        self.name() == "slot"
+    }
+
+    fn has_id(&self, id: &CssLocalName, case_sensitivity: CaseSensitivity) -> bool {
+        self.doc
+            .attr(self.id, "id")
+            .is_some_and(|value| case_sensitivity.eq(value.as_bytes(), id.0.as_bytes()))
+    }
+
+    fn has_class(&self, name: &CssLocalName, case_sensitivity: CaseSensitivity) -> bool {
+        self.doc.attr(self.id, "class").is_some_and(|value| {
+            value
+                .split_ascii_whitespace()
+                .any(|class| case_sensitivity.eq(class.as_bytes(), name.0.as_bytes()))
+        })
+    }
+
+    fn has_custom_state(&self, _name: &CssLocalName) -> bool {
+        false
+    }
+
+    fn imported_part(&self, _name: &CssLocalName) -> Option<CssLocalName> {
+        None
+    }
+
+    fn is_part(&self, _name: &CssLocalName) -> bool {
+        false
+    }
+
+    fn is_empty(&self) -> bool {
+        self.doc
+            .node(self.id)
+            .children
+            .iter()
+            .all(|&child| match &self.doc.node(child).data {
+                NodeData::Element { .. } => false,
+                NodeData::Text(text) => text.is_empty(),
+                NodeData::Document | NodeData::Comment(_) => true,
+            })
+    }
+
+    fn is_root(&self) -> bool {
+        self.doc.node(self.id).parent == Some(Document::ROOT)
+    }
+
+    fn add_element_unique_hashes(&self, _filter: &mut BloomFilter) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod css_tests {
+    use super::*;
+
+    const HTML: &str = r#"<ul id="list">
+<li class="a first">1</li><li class="b" data-x="en-US">2</li><li class="a">3</li>
+<li lang="EN"><span>4</span></li><li></li>
+</ul><p>after</p>"#;
+
+    const PAGE: &str = concat!(
+        r#"<div id="main" class="box"><h1 title="Main Title">T</h1>"#,
+        r#"<p class="intro lead" lang="en-GB">P1</p><p data-v="a b c">P2</p><span>S1</span>"#,
+        r#"<p class="x" data-url="https://example.com/file.pdf">P3</p>"#,
+        r#"<ul><li>L1</li><li class="odd">L2</li><li>L3</li><li>L4</li><li>L5</li></ul></div>"#,
+        r#"<section><p>Q1</p><em></em><b><!--c--></b><i id="a.b" class="1st">E</i></section>"#,
+    );
+
+    fn texts(css: &str) -> Vec<String> {
+        select_texts(HTML, css)
+    }
+
+    fn select_texts(html: &str, css: &str) -> Vec<String> {
+        let doc = Document::parse(html);
+        let selector = SelectorList::parse(css).unwrap();
+        selector
+            .select(&doc, Document::ROOT)
+            .map(|id| doc.text(id))
+            .collect()
+    }
+
+    fn check(cases: &[(&str, &[&str])]) {
+        for &(css, expected) in cases {
+            assert_eq!(select_texts(PAGE, css), expected, "{:?}", css);
+        }
+    }
+
+    #[test]
+    fn test_simple_selectors() {
+        assert_eq!(texts("li.a"), vec!["1", "3"]);
+        assert_eq!(texts("#list > .b"), vec!["2"]);
+        assert_eq!(texts(".a.first, li:empty"), vec!["1", ""]);
+        assert_eq!(texts("[data-x|=en]"), vec!["2"]);
+        assert_eq!(texts("[lang=en i]"), vec!["4"]);
+        // lang is case-insensitive in HTML, unless the selector says otherwise
+        assert_eq!(texts("[lang=en]"), vec!["4"]);
+        assert_eq!(texts("[lang=en s]"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_combinators_and_pseudo_classes() {
+        assert_eq!(texts("ul span"), vec!["4"]);
+        assert_eq!(texts(".first + li"), vec!["2"]);
+        assert_eq!(texts(".b ~ li:not(:empty)"), vec!["3", "4"]);
+        assert_eq!(texts("li:nth-child(2n+1)"), vec!["1", "3", ""]);
+        assert_eq!(texts("li:nth-last-child(2)"), vec!["4"]);
+        assert_eq!(texts("li:has(span)"), vec!["4"]);
+        assert_eq!(texts("ul ~ p:last-of-type"), vec!["after"]);
+    }
+
+    #[test]
+    fn test_invalid_selectors() {
+        for css in ["", "li >", "li[", ":unknown", "li:nth-child(x)", "a,"] {
+            assert!(SelectorList::parse(css).is_err(), "{:?}", css);
+        }
+    }
+
+    #[test]
+    fn test_attribute_operators() {
+        check(&[
+            ("[title]", &["T"]),
+            ("[title='Main Title']", &["T"]),
+            ("[ title = 'Main Title' ]", &["T"]),
+            ("[title=\"main title\" i]", &["T"]),
+            ("[title='main title']", &[]),
+            ("[title='main title' s]", &[]),
+            ("[TITLE]", &["T"]),
+            ("[data-v~=b]", &["P2"]),
+            ("[data-v~='a b']", &[]),
+            ("[class~=lead]", &["P1"]),
+            ("[lang|=en]", &["P1"]),
+            ("[lang|=en-GB]", &["P1"]),
+            ("[lang|=e]", &[]),
+            ("[data-url^=https]", &["P3"]),
+            ("[data-url^='']", &[]),
+            ("[data-url$='.pdf']", &["P3"]),
+            ("[data-url$='.PDF' i]", &["P3"]),
+            ("[data-url*=example]", &["P3"]),
+            ("[data-url*='']", &[]),
+            ("p[data-v][data-url]", &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_combinators() {
+        check(&[
+            ("div p", &["P1", "P2", "P3"]),
+            ("section p", &["Q1"]),
+            ("#main > p", &["P1", "P2", "P3"]),
+            ("#main>h1+p", &["P1"]),
+            ("body > p", &[]),
+            ("h1 + p", &["P1"]),
+            ("span + p", &["P3"]),
+            ("p + p", &["P2"]),
+            ("h1 ~ p", &["P1", "P2", "P3"]),
+            ("span ~ p", &["P3"]),
+            ("div > ul > li.odd", &["L2"]),
+            ("li + li + li", &["L3", "L4", "L5"]),
+            ("div ul ~ li", &[]),
+            ("span, h1", &["T", "S1"]),
+        ]);
+    }
+
+    #[test]
+    fn test_pseudo_classes() {
+        check(&[
+            ("li:first-child", &["L1"]),
+            ("LI:FIRST-CHILD", &["L1"]),
+            ("li:last-child", &["L5"]),
+            ("#main > :first-child", &["T"]),
+            ("li:only-child", &[]),
+            ("p:first-of-type", &["P1", "Q1"]),
+            ("p:last-of-type", &["P3", "Q1"]),
+            ("p:only-of-type", &["Q1"]),
+            ("li:nth-child(odd)", &["L1", "L3", "L5"]),
+            ("li:nth-child(even)", &["L2", "L4"]),
+            ("li:nth-child(3)", &["L3"]),
+            ("li:nth-child( -n + 2 )", &["L1", "L2"]),
+            ("li:nth-child(n+4)", &["L4", "L5"]),
+            ("li:nth-child(3n)", &["L3"]),
+            ("li:nth-last-child(1)", &["L5"]),
+            ("p:nth-of-type(2)", &["P2"]),
+            ("p:nth-last-of-type(1)", &["P3", "Q1"]),
+            ("section > :empty", &["", ""]),
+            ("p:not(.x)", &["P1", "P2", "Q1"]),
+            ("p:not(.x, [lang])", &["P2", "Q1"]),
+            ("p:is(.x, [lang])", &["P1", "P3"]),
+            (":where(h1, span)", &["T", "S1"]),
+            ("section:has(em) > p", &["Q1"]),
+            ("ul:has(.odd) li:nth-child(2)", &["L2"]),
+            ("div:not(:has(li))", &[]),
+        ]);
+        let doc = Document::parse(PAGE);
+        let roots: Vec<_> = SelectorList::parse(":root")
+            .unwrap()
+            .select(&doc, Document::ROOT)
+            .collect();
+        assert_eq!(roots.len(), 1);
+        assert_eq!(doc.name(roots[0]), Some("html"));
+    }
+
+    #[test]
+    fn test_escapes() {
+        check(&[
+            ("#a\\.b", &["E"]),
+            (".\\31 st", &["E"]),
+            ("[id='a.b']", &["E"]),
+            ("[id=\"a\\2e b\"]", &["E"]),
+            ("[id='a\\'b']", &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            " ",
+            "> li",
+            ",a",
+            "a,,b",
+            "a)",
+            "#",
+            ".",
+            "[=x]",
+            "[a==b]",
+            "[a=b x]",
+            "[a!=b]",
+            "::before",
+            "li:first-child()",
+            "li:nth-child(2n+)",
+            "li:not()",
+        ];
+        for css in cases {
+            assert!(SelectorList::parse(css).is_err(), "{:?}", css);
+        }
+        let err = SelectorList::parse("li[").unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Invalid CSS selector \"li[\": Unexpected EOL at position 3"
+        );
+    }
+}
@@ -0,0 +1,462 @@
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use anyhow::{anyhow, Result};
+use async_compression::tokio::write::{BrotliDecoder, GzipDecoder, ZlibDecoder, ZstdDecoder};
+use bytes::Bytes;
+use rquest::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH};
+use rquest::Url;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Content codings that are decompressed.
+pub const ENCODINGS: &[&str] = &["gzip", "br", "zstd", "deflate"];
+
+/// Most bytes allocated upfront for a body, whatever its `Content-Length` claims.
+const MAX_PREALLOCATION: u64 = 64 * 1024;
+
+/// The `Accept-Encoding` header `rquest` sends when all the codings are enabled.
+pub const DEFAULT_ACCEPT_ENCODING: HeaderValue =
+    HeaderValue::from_static("gzip, br, zstd, deflate");
+
+/// The `Accept-Encoding` header advertising `encodings`, or only "identity" if it is empty.
+pub fn accept_encoding_header(encodings: &[String]) -> Result<HeaderValue> {
+    if let Some(encoding) = encodings
+        .iter()
+        .find(|encoding| !ENCODINGS.contains(&encoding.as_str()))
+    {
+        return Err(anyhow!(
+            "Invalid accept_encoding: {:?}, expected one of {:?}",
+            encoding,
+            ENCODINGS
+        ));
+    }
+    Ok(match encodings.is_empty() {
+        true => HeaderValue::from_static("identity"),
+        false => HeaderValue::from_str(&encodings.join(", "))?,
+    })
+}
+
+/// A response body that can't be decompressed.
+#[derive(Debug)]
+pub struct DecompressError {
+    pub encoding: &'static str,
+    pub source: io::Error,
+}
+
+impl fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {} body: {}", self.encoding, self.source)
+    }
+}
+
+impl std::error::Error for DecompressError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Limits of the size of response bodies, in bytes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BodyLimits {
+    /// Size of the body as received, compressed or not.
+    pub max_content_length: Option<u64>,
+    /// Size of the decompressed body.
+    pub max_decompressed_size: Option<u64>,
+}
+
+/// A response body over one of the `BodyLimits`.
+#[derive(Debug)]
+pub struct BodyTooLargeError {
+    pub limit: u64,
+    pub decompressed: bool,
+}
+
+impl fmt::Display for BodyTooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.decompressed {
+            true => write!(
+                f,
+                "decompressed response body larger than max_decompressed_size ({} bytes)",
+                self.limit
+            ),
+            false => write!(
+                f,
+                "response body larger than max_content_length ({} bytes)",
+                self.limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BodyTooLargeError {}
+
+/// Output of a decoder, which fails rather than growing past `limit`: the decoders write it
+/// in small pieces, so a decompression bomb is stopped without being held in memory.
+#[derive(Default)]
+struct Output {
+    buf: Vec<u8>,
+    written: u64,
+    limit: Option<u64>,
+    exceeded: bool,
+}
+
+impl AsyncWrite for Output {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.written += buf.len() as u64;
+        if this.limit.is_some_and(|limit| this.written > limit) {
+            this.exceeded = true;
+            return Poll::Ready(Err(io::Error::other("max_decompressed_size exceeded")));
+        }
+        this.buf.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+enum Codec {
+    Gzip(GzipDecoder<Output>),
+    Brotli(BrotliDecoder<Output>),
+    Zstd(ZstdDecoder<Output>),
+    Deflate(ZlibDecoder<Output>),
+}
+
+impl Codec {
+    fn writer(&mut self) -> &mut (dyn AsyncWrite + Send + Unpin) {
+        match self {
+            Codec::Gzip(decoder) => decoder,
+            Codec::Brotli(decoder) => decoder,
+            Codec::Zstd(decoder) => decoder,
+            Codec::Deflate(decoder) => decoder,
+        }
+    }
+
+    fn output(&mut self) -> &mut Output {
+        match self {
+            Codec::Gzip(decoder) => decoder.get_mut(),
+            Codec::Brotli(decoder) => decoder.get_mut(),
+            Codec::Zstd(decoder) => decoder.get_mut(),
+            Codec::Deflate(decoder) => decoder.get_mut(),
+        }
+    }
+}
+
+/// Decompressor of a response body, fed chunk by chunk.
+///
+/// Bodies are decompressed here rather than by `rquest`, whose decompression is set for the
+/// whole client, so that `decompress=False` can return the raw body of a single request.
+pub struct BodyDecoder {
+    // Boxed, the decoder states are large
+    codec: Box<Codec>,
+    encoding: &'static str,
+    // Nothing was written yet: an empty body, e.g. of a HEAD request, is left empty
+    empty: bool,
+}
+
+impl BodyDecoder {
+    /// The decoder of the `Content-Encoding` of `headers`. `Content-Encoding` and
+    /// `Content-Length` are removed, as they don't apply to the decompressed body.
+    ///
+    /// None if the body is not encoded, or not with a single supported coding: it is left as is.
+    /// Decompressing more than `limit` bytes fails with a `BodyTooLargeError`.
+    pub fn detect(headers: &mut HeaderMap, limit: Option<u64>) -> Option<Self> {
+        let (codec, encoding) = {
+            let mut codings = headers
+                .get_all(CONTENT_ENCODING)
+                .iter()
+                .flat_map(|value| value.to_str().unwrap_or("?").split(','))
+                .map(str::trim)
+                .filter(|coding| !coding.is_empty() && !coding.eq_ignore_ascii_case("identity"));
+            let coding = codings.next()?.to_ascii_lowercase();
+            if codings.next().is_some() {
+                return None;
+            }
+            let output = Output {
+                limit,
+                ..Default::default()
+            };
+            match coding.as_str() {
+                "gzip" | "x-gzip" => (Codec::Gzip(GzipDecoder::new(output)), "gzip"),
+                "br" => (Codec::Brotli(BrotliDecoder::new(output)), "br"),
+                "zstd" => (Codec::Zstd(ZstdDecoder::new(output)), "zstd"),
+                "deflate" => (Codec::Deflate(ZlibDecoder::new(output)), "deflate"),
+                _ => return None,
+            }
+        };
+        headers.remove(CONTENT_ENCODING);
+        headers.remove(CONTENT_LENGTH);
+        Some(BodyDecoder {
+            codec: Box::new(codec),
+            encoding,
+            empty: true,
+        })
+    }
+
+    /// Decompresses a chunk of the body.
+    pub async fn decode(&mut self, chunk: &[u8]) -> Result<Bytes> {
+        if chunk.is_empty() {
+            return Ok(Bytes::new());
+        }
+        self.empty = false;
+        let writer = self.codec.writer();
+        let result = match writer.write_all(chunk).await {
+            Ok(()) => writer.flush().await,
+            Err(err) => Err(err),
+        };
+        self.output(result)
+    }
+
+    /// Ends the body, checking that it is complete. Returns the last decompressed bytes.
+    pub async fn finish(&mut self) -> Result<Bytes> {
+        if self.empty {
+            return Ok(Bytes::new());
+        }
+        let result = self.codec.writer().shutdown().await;
+        self.output(result)
+    }
+
+    fn output(&mut self, result: io::Result<()>) -> Result<Bytes> {
+        let output = self.codec.output();
+        if let (true, Some(limit)) = (output.exceeded, output.limit) {
+            return Err(BodyTooLargeError {
+                limit,
+                decompressed: true,
+            }
+            .into());
+        }
+        result.map_err(|source| DecompressError {
+            encoding: self.encoding,
+            source,
+        })?;
+        Ok(Bytes::from(std::mem::take(&mut output.buf)))
+    }
+}
+
+/// The body of a response, decompressed by a `BodyDecoder` if there is one.
+pub struct BodyReader {
+    resp: rquest::Response,
+    decoder: Option<BodyDecoder>,
+    eof: bool,
+    // Bytes received, and their limit
+    received: u64,
+    max_content_length: Option<u64>,
+}
+
+impl BodyReader {
+    /// Fails right away if the `Content-Length` of `resp` is over `max_content_length`.
+    pub fn new(
+        resp: rquest::Response,
+        decoder: Option<BodyDecoder>,
+        max_content_length: Option<u64>,
+    ) -> Result<Self> {
+        let content_length = resp.content_length();
+        if let (Some(limit), Some(content_length)) = (max_content_length, content_length) {
+            if content_length > limit {
+                return Err(BodyTooLargeError {
+                    limit,
+                    decompressed: false,
+                }
+                .into());
+            }
+        }
+        Ok(BodyReader {
+            resp,
+            decoder,
+            eof: false,
+            received: 0,
+            max_content_length,
+        })
+    }
+
+    pub fn url(&self) -> &Url {
+        self.resp.url()
+    }
+
+    /// The length of the body, if it is known and not decompressed.
+    pub fn content_length(&self) -> Option<u64> {
+        match self.decoder {
+            Some(_) => None,
+            None => self.resp.content_length(),
+        }
+    }
+
+    /// Reads the next chunk of the body. None at the end of the body.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>> {
+        let Some(decoder) = &mut self.decoder else {
+            return raw_chunk(&mut self.resp, &mut self.received, self.max_content_length).await;
+        };
+        while !self.eof {
+            let chunk = raw_chunk(&mut self.resp, &mut self.received, self.max_content_length);
+            let decoded = match chunk.await? {
+                Some(chunk) => decoder.decode(&chunk).await?,
+                None => {
+                    self.eof = true;
+                    decoder.finish().await?
+                }
+            };
+            // A chunk can be too short to decompress anything
+            if !decoded.is_empty() {
+                return Ok(Some(decoded));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads the rest of the body.
+    pub async fn read_to_end(&mut self) -> Result<Vec<u8>> {
+        // The server can claim any length: the buffer grows with what is actually received
+        let capacity = self.content_length().unwrap_or(0).min(MAX_PREALLOCATION);
+        let mut buf = Vec::with_capacity(capacity as usize);
+        while let Some(chunk) = self.chunk().await? {
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf)
+    }
+}
+
+/// Reads the next chunk of the body of `resp` as received, adding its length to `received`.
+async fn raw_chunk(
+    resp: &mut rquest::Response,
+    received: &mut u64,
+    max_content_length: Option<u64>,
+) -> Result<Option<Bytes>> {
+    let chunk = resp.chunk().await?;
+    if let Some(chunk) = &chunk {
+        *received += chunk.len() as u64;
+        if let Some(limit) = max_content_length.filter(|&limit| *received > limit) {
+            return Err(BodyTooLargeError {
+                limit,
+                decompressed: false,
+            }
+            .into());
+        }
+    }
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod decoder_tests {
+    use super::*;
+    use async_compression::tokio::write::GzipEncoder;
+
+    async fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzipEncoder::new(Vec::new());
+        encoder.write_all(data).await.unwrap();
+        encoder.shutdown().await.unwrap();
+        encoder.into_inner()
+    }
+
+    fn headers(content_encoding: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_ENCODING,
+            HeaderValue::from_str(content_encoding).unwrap(),
+        );
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("42"));
+        headers
+    }
+
+    #[test]
+    fn test_detect() {
+        for encoding in ["gzip", "X-Gzip", "br", "zstd", "deflate", "identity, gzip"] {
+            let mut headers = headers(encoding);
+            assert!(
+                BodyDecoder::detect(&mut headers, None).is_some(),
+                "{}",
+                encoding
+            );
+            assert!(headers.is_empty());
+        }
+        for encoding in ["", "identity", "compress", "gzip, br"] {
+            let mut headers = headers(encoding);
+            assert!(
+                BodyDecoder::detect(&mut headers, None).is_none(),
+                "{}",
+                encoding
+            );
+            assert_eq!(headers.len(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode() {
+        let data = "primp ".repeat(10_000);
+        let compressed = gzip(data.as_bytes()).await;
+        let mut decoder = BodyDecoder::detect(&mut headers("gzip"), None).unwrap();
+        let mut decoded = Vec::new();
+        for chunk in compressed.chunks(100) {
+            decoded.extend_from_slice(&decoder.decode(chunk).await.unwrap());
+        }
+        decoded.extend_from_slice(&decoder.finish().await.unwrap());
+        assert_eq!(decoded, data.as_bytes());
+
+        // Empty bodies are left empty, truncated ones are errors
+        let mut decoder = BodyDecoder::detect(&mut headers("gzip"), None).unwrap();
+        assert!(decoder.finish().await.unwrap().is_empty());
+        let mut decoder = BodyDecoder::detect(&mut headers("gzip"), None).unwrap();
+        decoder.decode(&compressed[..50]).await.unwrap();
+        let err = decoder.finish().await.unwrap_err();
+        assert!(err.is::<DecompressError>());
+    }
+
+    #[tokio::test]
+    async fn test_decompressed_size_limit() {
+        // 10 MB of zeros, compressed to about 10 KB, in a single chunk
+        let compressed = gzip(&vec![0; 10_000_000]).await;
+        let mut decoder = BodyDecoder::detect(&mut headers("gzip"), Some(100_000)).unwrap();
+        let err = decoder.decode(&compressed).await.unwrap_err();
+        let err = err.downcast_ref::<BodyTooLargeError>().unwrap();
+        assert!(err.decompressed && err.limit == 100_000);
+        assert!(decoder.codec.output().buf.len() <= 100_000);
+
+        let mut decoder = BodyDecoder::detect(&mut headers("gzip"), Some(10_000_000)).unwrap();
+        let decoded = decoder.decode(&compressed).await.unwrap();
+        assert_eq!(
+            decoded.len() + decoder.finish().await.unwrap().len(),
+            10_000_000
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_to_end_with_false_content_length() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await;
+            let head = "HTTP/1.1 200 OK\r\nContent-Length: 1000000000000000\r\n\r\nshort";
+            stream.write_all(head.as_bytes()).await.unwrap();
+        });
+        let resp = rquest::Client::new()
+            .get(format!("http://127.0.0.1:{}/", port))
+            .send()
+            .await
+            .unwrap();
+        let mut reader = BodyReader::new(resp, None, None).unwrap();
+        assert_eq!(reader.content_length(), Some(1_000_000_000_000_000));
+        // The truncated body is an error, not an allocation of the claimed length
+        assert!(reader.read_to_end().await.is_err());
+    }
+
+    #[test]
+    fn test_accept_encoding() {
+        let encodings = ["br".to_string(), "gzip".to_string()];
+        assert_eq!(accept_encoding_header(&encodings).unwrap(), "br, gzip");
+        assert_eq!(accept_encoding_header(&[]).unwrap(), "identity");
+        assert!(accept_encoding_header(&["compress".to_string()]).is_err());
+    }
+}
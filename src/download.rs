@@ -0,0 +1,216 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use pyo3::prelude::*;
+use rquest::{
+    header::{HeaderValue, ACCEPT_ENCODING, CONTENT_RANGE, RANGE},
+    RequestBuilder, StatusCode,
+};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+use crate::decoder::{BodyReader, BodyTooLargeError};
+use crate::limiter::HostLimiter;
+use crate::redirect::{send_following_redirects, Sent};
+use crate::retry::{send_with_retry, Retry};
+use crate::utils::{parse_content_range, ContentRange};
+
+/// A download answered with a status other than 2xx.
+#[derive(Debug)]
+pub struct DownloadStatusError {
+    pub status: StatusCode,
+    pub url: String,
+}
+
+impl fmt::Display for DownloadStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Download failed with status '{} {}' for url '{}'",
+            self.status.as_u16(),
+            self.status.canonical_reason().unwrap_or_default(),
+            self.url
+        )
+    }
+}
+
+impl std::error::Error for DownloadStatusError {}
+
+/// Summary of a finished `download`.
+#[pyclass]
+pub struct DownloadResult {
+    #[pyo3(get)]
+    pub bytes_written: u64,
+    #[pyo3(get)]
+    pub resumed: bool,
+    #[pyo3(get)]
+    pub url: String,
+}
+
+#[pymethods]
+impl DownloadResult {
+    fn __repr__(&self) -> String {
+        format!(
+            "DownloadResult(bytes_written={}, resumed={}, url={:?})",
+            self.bytes_written, self.resumed, self.url
+        )
+    }
+}
+
+/// Sends `request_builder` and writes the response body to `path`, chunk by chunk.
+///
+/// If `resume` is set and `path` already holds part of the body, only the rest of it is requested
+/// with a `Range` header. Servers answering without `206 Partial Content` get the file rewritten
+/// from scratch. The written length is checked against `Content-Length` / `Content-Range`.
+///
+/// The body is written as received, never decompressed: byte offsets of a resumed download must
+/// be those of the representation the server sends. `Accept-Encoding: identity` is requested,
+/// and a body compressed anyway is saved compressed, like `curl` does without `--compressed`.
+/// Files larger than `max_content_length`, a resumed one with its partial part included, fail
+/// with a `BodyTooLargeError`: being written as received, they are only subject to this limit,
+/// not to `max_decompressed_size`.
+///
+/// Redirects, host limits and the `retry` policy are applied as for other requests, see
+/// `send_following_redirects`.
+pub async fn download_to_file(
+    request_builder: RequestBuilder,
+    path: PathBuf,
+    resume: bool,
+    max_redirects: Option<usize>,
+    referer: bool,
+    limiter: Option<Arc<HostLimiter>>,
+    retry: Option<Retry>,
+    max_content_length: Option<u64>,
+) -> Result<DownloadResult> {
+    let offset = match fs::metadata(&path).await {
+        Ok(metadata) if resume && metadata.is_file() => metadata.len(),
+        _ => 0,
+    };
+    let (client, request) = request_builder.build_split();
+    let mut request = request?;
+    let headers = request.headers_mut();
+    headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+    if offset > 0 {
+        headers.insert(RANGE, HeaderValue::from_str(&format!("bytes={}-", offset))?);
+    }
+    let request_builder = RequestBuilder::from_parts(client, request);
+
+    let send = |request_builder, limit| send_with_retry(request_builder, retry.clone(), limit);
+    let Sent {
+        resp,
+        // Slot of the host, held until the body is written
+        permit: _permit,
+        ..
+    } = send_following_redirects(request_builder, max_redirects, referer, limiter, send).await?;
+    let status = resp.status();
+    let url = resp.url().to_string();
+    let content_range = resp
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_content_range);
+
+    // The partial file already holds the whole body
+    if offset > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE {
+        if let Some(ContentRange {
+            range: None,
+            total: Some(total),
+        }) = content_range
+        {
+            if total == offset {
+                tracing::info!("download: {} already complete ({} bytes)", url, offset);
+                return Ok(DownloadResult {
+                    bytes_written: 0,
+                    resumed: true,
+                    url,
+                });
+            }
+        }
+    }
+    if !status.is_success() {
+        return Err(DownloadStatusError { status, url }.into());
+    }
+
+    let resumed = offset > 0 && status == StatusCode::PARTIAL_CONTENT;
+    let (expected, total) = if resumed {
+        match content_range {
+            Some(ContentRange {
+                range: Some((start, end)),
+                total,
+            }) if start == offset && end >= start => (Some(end - start + 1), total),
+            _ => {
+                return Err(anyhow!(
+                    "Invalid Content-Range for a download resumed at byte {}",
+                    offset
+                ))
+            }
+        }
+    } else {
+        (resp.content_length(), resp.content_length())
+    };
+
+    // The limit applies to the whole file, the partial one included
+    let mut limit = max_content_length;
+    if resumed {
+        if let Some(max_content_length) = max_content_length {
+            let size = total.or_else(|| expected.map(|expected| offset + expected));
+            if size.is_some_and(|size| size > max_content_length) {
+                return Err(BodyTooLargeError {
+                    limit: max_content_length,
+                    decompressed: false,
+                }
+                .into());
+            }
+            limit = Some(max_content_length.saturating_sub(offset));
+        }
+    }
+    let mut body = BodyReader::new(resp, None, limit)?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&path)
+        .await?;
+    let mut bytes_written: u64 = 0;
+    while let Some(chunk) = body.chunk().await? {
+        file.write_all(&chunk).await?;
+        bytes_written += chunk.len() as u64;
+    }
+    file.flush().await?;
+
+    if let Some(expected) = expected {
+        if bytes_written != expected {
+            return Err(anyhow!(
+                "Incomplete download: expected {} bytes, received {}",
+                expected,
+                bytes_written
+            ));
+        }
+    }
+    if let Some(total) = total {
+        let size = file.metadata().await?.len();
+        if size != total {
+            return Err(anyhow!(
+                "Incomplete download: expected a {} bytes file, got {}",
+                total,
+                size
+            ));
+        }
+    }
+
+    tracing::info!(
+        "download: {} {} {} bytes",
+        url,
+        status.as_u16(),
+        bytes_written
+    );
+    Ok(DownloadResult {
+        bytes_written,
+        resumed,
+        url,
+    })
+}
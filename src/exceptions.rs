@@ -0,0 +1,332 @@
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyTimeoutError};
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyDict, PyTuple, PyType};
+
+use crate::decoder::{BodyTooLargeError, DecompressError};
+use crate::download::DownloadStatusError;
+use crate::redirect::{RequestTimeoutError, TooManyRedirectsError};
+use crate::response::Response;
+
+create_exception!(
+    primp,
+    RequestError,
+    PyException,
+    "Base class of the errors raised while making a request."
+);
+create_exception!(
+    primp,
+    ConnectError,
+    RequestError,
+    "Failed to connect to the server, DNS resolution failures included."
+);
+create_exception!(
+    primp,
+    ProxyError,
+    RequestError,
+    "Failed to connect through the proxy."
+);
+create_exception!(primp, TLSError, RequestError, "The TLS handshake failed.");
+create_exception!(
+    primp,
+    TooManyRedirects,
+    RequestError,
+    "Following redirects failed: too many redirects or a redirect loop."
+);
+create_exception!(
+    primp,
+    DecodeError,
+    RequestError,
+    "Failed to decode the response body."
+);
+create_exception!(
+    primp,
+    ResponseTooLarge,
+    RequestError,
+    "The response body is larger than `max_content_length` or `max_decompressed_size`."
+);
+create_exception!(primp, InvalidURL, RequestError, "The URL is invalid.");
+create_exception!(
+    primp,
+    HTTPStatusError,
+    RequestError,
+    "The response has a client error (4xx) or server error (5xx) status code."
+);
+
+static TIMEOUT_ERROR: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+
+/// `primp.TimeoutError`: "The request timed out."
+///
+/// It subclasses both `RequestError` and the builtin `TimeoutError`, so that `except
+/// TimeoutError` catches it too. `create_exception!` only takes one base class, so the type is
+/// created with `type(name, bases, dict)`.
+fn timeout_error_type(py: Python<'_>) -> &Bound<'_, PyType> {
+    TIMEOUT_ERROR
+        .get_or_try_init(py, || -> PyResult<_> {
+            let bases = PyTuple::new(
+                py,
+                [
+                    py.get_type::<RequestError>(),
+                    py.get_type::<PyTimeoutError>(),
+                ],
+            )?;
+            let dict = PyDict::new(py);
+            dict.set_item("__module__", "primp")?;
+            dict.set_item("__doc__", "The request timed out.")?;
+            let ty = py
+                .get_type::<PyType>()
+                .call1(("TimeoutError", bases, dict))?
+                .downcast_into::<PyType>()?;
+            Ok(ty.unbind())
+        })
+        .expect("failed to create primp.TimeoutError")
+        .bind(py)
+}
+
+/// Creates a `primp.TimeoutError` with the message `msg`.
+fn timeout_error(py: Python<'_>, msg: String) -> PyErr {
+    PyErr::from_type(timeout_error_type(py).clone(), msg)
+}
+
+/// Registers the exception hierarchy in the module.
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("RequestError", py.get_type::<RequestError>())?;
+    m.add("TimeoutError", timeout_error_type(py))?;
+    m.add("ConnectError", py.get_type::<ConnectError>())?;
+    m.add("ProxyError", py.get_type::<ProxyError>())?;
+    m.add("TLSError", py.get_type::<TLSError>())?;
+    m.add("TooManyRedirects", py.get_type::<TooManyRedirects>())?;
+    m.add("DecodeError", py.get_type::<DecodeError>())?;
+    m.add("ResponseTooLarge", py.get_type::<ResponseTooLarge>())?;
+    m.add("InvalidURL", py.get_type::<InvalidURL>())?;
+    m.add("HTTPStatusError", py.get_type::<HTTPStatusError>())?;
+    Ok(())
+}
+
+/// Words in the error messages of a failed connection through a proxy.
+const PROXY_NEEDLES: &[&str] = &["proxy", "tunnel", "socks"];
+
+/// Messages of the URL errors of `rquest` which have no public type: a scheme other than http
+/// and https, a URL which is not a valid URI, and a URL without a host.
+const URL_ERROR_MESSAGES: &[&str] = &[
+    "URL scheme is not allowed",
+    "url is not a valid uri",
+    "no host in url",
+];
+
+/// Whether an error below `err` in the source chain is an invalid URL: a parse error, or one of
+/// the URL errors of `rquest`.
+fn sources_have_url_error(err: &dyn std::error::Error) -> bool {
+    let mut source = err.source();
+    while let Some(err) = source {
+        if err.is::<url::ParseError>() || URL_ERROR_MESSAGES.contains(&err.to_string().as_str()) {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Whether an error below `err` in the source chain is a BoringSSL error, which the TLS
+/// connector returns when the handshake fails.
+fn sources_have_tls_error(err: &dyn std::error::Error) -> bool {
+    let mut source = err.source();
+    while let Some(err) = source {
+        if err.is::<boring2::ssl::Error>() || err.is::<boring2::error::ErrorStack>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Whether the message of an error below `err` in the source chain contains one of `needles`.
+/// The message of `err` itself is skipped: the one of `rquest::Error` includes the URL.
+fn sources_contain(err: &dyn std::error::Error, needles: &[&str]) -> bool {
+    let mut source = err.source();
+    while let Some(err) = source {
+        let msg = err.to_string().to_ascii_lowercase();
+        if needles.iter().any(|needle| msg.contains(needle)) {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Maps a failed `rquest` request to the exception hierarchy.
+///
+/// `rquest::Error` doesn't tell proxy and TLS failures apart from other connect errors.
+/// TLS failures are recognized from the BoringSSL errors in its sources. Proxy failures have
+/// no error type of their own, `rquest` reports them with plain messages: the connect errors of
+/// requests sent through the proxy of the client (`proxied`) are proxy errors. As a last resort,
+/// for the proxies `rquest` picks up from the environment, the messages of the sources are
+/// searched for `PROXY_NEEDLES`.
+fn classify(py: Python, err: &anyhow::Error, rquest_err: &rquest::Error, proxied: bool) -> PyErr {
+    let msg = format!("{:#}", err);
+    if rquest_err.is_timeout() {
+        timeout_error(py, msg)
+    } else if rquest_err.is_redirect() {
+        TooManyRedirects::new_err(msg)
+    } else if rquest_err.is_decode() {
+        DecodeError::new_err(msg)
+    } else if rquest_err.is_builder() && sources_have_url_error(rquest_err) {
+        InvalidURL::new_err(msg)
+    } else if rquest_err.is_connect() {
+        if sources_have_tls_error(rquest_err) {
+            TLSError::new_err(msg)
+        } else if proxied || sources_contain(rquest_err, PROXY_NEEDLES) {
+            ProxyError::new_err(msg)
+        } else {
+            ConnectError::new_err(msg)
+        }
+    } else {
+        RequestError::new_err(msg)
+    }
+}
+
+/// Converts an error of the request future into a Python exception carrying the request
+/// `url` and `method` as attributes.
+///
+/// Python exceptions raised by callbacks of the request, e.g. the iterator of a streamed
+/// upload, are re-raised as is. Other errors are mapped to `RequestError` and its subclasses.
+/// `proxied` tells whether the request was sent through the proxy of the client.
+pub fn request_error(
+    py: Python,
+    err: anyhow::Error,
+    method: &str,
+    url: &str,
+    proxied: bool,
+) -> PyErr {
+    if let Some(py_err) = err.chain().find_map(|e| e.downcast_ref::<PyErr>()) {
+        return py_err.clone_ref(py);
+    }
+
+    let rquest_err = err.chain().find_map(|e| e.downcast_ref::<rquest::Error>());
+    let py_err = match rquest_err {
+        Some(rquest_err) => classify(py, &err, rquest_err, proxied),
+        None if err.is::<TooManyRedirectsError>() => {
+            TooManyRedirects::new_err(format!("{:#}", err))
+        }
+        None if err.is::<RequestTimeoutError>() => timeout_error(py, format!("{:#}", err)),
+        None if err.is::<DecompressError>() => DecodeError::new_err(format!("{:#}", err)),
+        None if err.is::<BodyTooLargeError>() => ResponseTooLarge::new_err(format!("{:#}", err)),
+        None if err.is::<DownloadStatusError>() => HTTPStatusError::new_err(format!("{:#}", err)),
+        None => RequestError::new_err(format!("{:#}", err)),
+    };
+    let url = rquest_err
+        .and_then(|e| e.url())
+        .map_or_else(|| url.to_string(), |u| u.to_string());
+    let value = py_err.value(py);
+    // Setting attributes on a fresh exception instance can't fail
+    let _ = value.setattr("url", url);
+    let _ = value.setattr("method", method);
+    // The body of a failed download is not read: there is no response
+    if let Some(status_err) = err.downcast_ref::<DownloadStatusError>() {
+        let _ = value.setattr("url", &status_err.url);
+        let _ = value.setattr("status_code", status_err.status.as_u16());
+        let _ = value.setattr("response", py.None());
+    }
+    py_err
+}
+
+/// Creates an `HTTPStatusError` carrying the `response`, its `status_code`, `url` and `method`
+/// as attributes.
+pub fn http_status_error(
+    response: &Bound<'_, Response>,
+    method: &str,
+    url: &str,
+    msg: String,
+) -> PyErr {
+    let py_err = HTTPStatusError::new_err(msg);
+    let value = py_err.value(response.py());
+    // Setting attributes on a fresh exception instance can't fail
+    let _ = value.setattr("response", response);
+    let _ = value.setattr("status_code", response.borrow().status_code);
+    let _ = value.setattr("url", url);
+    let _ = value.setattr("method", method);
+    py_err
+}
+
+#[cfg(test)]
+mod exceptions_tests {
+    use super::*;
+
+    /// An error with a message and an optional source, like the chain of `rquest::Error`.
+    #[derive(Debug)]
+    struct ChainError(
+        &'static str,
+        Option<Box<dyn std::error::Error + Send + Sync>>,
+    );
+
+    impl std::fmt::Display for ChainError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(self.0)
+        }
+    }
+
+    impl std::error::Error for ChainError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            self.1.as_deref().map(|e| e as _)
+        }
+    }
+
+    fn chain(messages: &[&'static str]) -> ChainError {
+        let (first, rest) = messages.split_first().unwrap();
+        ChainError(
+            first,
+            (!rest.is_empty()).then(|| Box::new(chain(rest)) as Box<_>),
+        )
+    }
+
+    #[test]
+    fn test_sources_have_tls_error() {
+        let err = ChainError(
+            "error sending request for url (https://example.com/)",
+            Some(Box::new(ChainError(
+                "client error (Connect)",
+                Some(Box::new(boring2::error::ErrorStack::get())),
+            ))),
+        );
+        assert!(sources_have_tls_error(&err));
+        // Messages alone don't make a TLS error
+        let err = chain(&[
+            "error sending request for url (https://sslvpn.example.com/)",
+            "client error (Connect)",
+            "error:1000007d:SSL routines:OPENSSL_internal:CERTIFICATE_VERIFY_FAILED",
+        ]);
+        assert!(!sources_have_tls_error(&err));
+    }
+
+    #[test]
+    fn test_sources_have_url_error() {
+        let parse_error = rquest::Url::parse("not a url").unwrap_err();
+        let err = ChainError("builder error", Some(Box::new(parse_error)));
+        assert!(sources_have_url_error(&err));
+        let err = chain(&[
+            "builder error for url (ftp://x/)",
+            "URL scheme is not allowed",
+        ]);
+        assert!(sources_have_url_error(&err));
+        // Other builder errors carry the URL too
+        let err = chain(&[
+            "builder error for url (https://example.com/)",
+            "invalid HTTP header name",
+        ]);
+        assert!(!sources_have_url_error(&err));
+    }
+
+    #[test]
+    fn test_sources_contain() {
+        // The URL in the top-level message is not looked at
+        for url in ["https://proxy.example.com/", "https://x/api/socks"] {
+            let message = format!("error sending request for url ({})", url).leak();
+            let err = chain(&[message, "client error (Connect)", "Connection refused"]);
+            assert!(!sources_contain(&err, PROXY_NEEDLES));
+        }
+        let err = chain(&["error sending request", "unsuccessful tunnel"]);
+        assert!(sources_contain(&err, PROXY_NEEDLES));
+    }
+}
@@ -0,0 +1,153 @@
+use foldhash::fast::RandomState;
+use indexmap::IndexMap;
+use pyo3::exceptions::PyKeyError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyIterator, PyList, PyString};
+use rquest::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::traits::HeadersTraits;
+
+/// Case-insensitive, multi-valued HTTP headers.
+///
+/// Works like a read-only dict: `headers[name]` and `get` join repeated headers with ", ",
+/// `get_all` and `items` keep every value. Values that are not valid UTF-8 are returned as bytes.
+#[pyclass(frozen)]
+#[derive(Clone, Debug, Default)]
+pub struct Headers {
+    map: HeaderMap,
+}
+
+impl From<HeaderMap> for Headers {
+    fn from(map: HeaderMap) -> Self {
+        Headers { map }
+    }
+}
+
+impl Headers {
+    pub fn header_map(&self) -> &HeaderMap {
+        &self.map
+    }
+
+    /// Single-valued view of the headers, with invalid UTF-8 replaced.
+    pub fn to_indexmap(&self) -> IndexMap<String, String, RandomState> {
+        self.map.to_indexmap()
+    }
+
+    /// Value of the header `name`, repeated values joined with ", ".
+    fn joined(&self, py: Python<'_>, name: &str) -> Option<PyObject> {
+        let name = HeaderName::from_bytes(name.as_bytes()).ok()?;
+        let values: Vec<&HeaderValue> = self.map.get_all(&name).iter().collect();
+        match values.as_slice() {
+            [] => None,
+            [value] => Some(value_to_py(py, value)),
+            values => {
+                let joined = values
+                    .iter()
+                    .map(|value| value.as_bytes())
+                    .collect::<Vec<_>>()
+                    .join(&b", "[..]);
+                Some(bytes_to_py(py, &joined))
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl Headers {
+    /// The value of the header `name`, or `default` if it is missing.
+    #[pyo3(signature = (name, default=None))]
+    fn get(&self, py: Python<'_>, name: &str, default: Option<PyObject>) -> Option<PyObject> {
+        self.joined(py, name).or(default)
+    }
+
+    /// All the values of the header `name`.
+    fn get_all(&self, py: Python<'_>, name: &str) -> Vec<PyObject> {
+        let Ok(name) = HeaderName::from_bytes(name.as_bytes()) else {
+            return Vec::new();
+        };
+        self.map
+            .get_all(&name)
+            .iter()
+            .map(|value| value_to_py(py, value))
+            .collect()
+    }
+
+    /// The `(name, value)` pairs, one per value, with lowercase names.
+    fn items(&self, py: Python<'_>) -> Vec<(String, PyObject)> {
+        self.map
+            .iter()
+            .map(|(name, value)| (name.as_str().to_string(), value_to_py(py, value)))
+            .collect()
+    }
+
+    /// The distinct header names, lowercase.
+    fn keys(&self) -> Vec<String> {
+        self.map
+            .keys()
+            .map(|name| name.as_str().to_string())
+            .collect()
+    }
+
+    /// The values of the distinct headers, repeated values joined with ", ".
+    fn values(&self, py: Python<'_>) -> Vec<PyObject> {
+        self.map
+            .keys()
+            .filter_map(|name| self.joined(py, name.as_str()))
+            .collect()
+    }
+
+    fn __getitem__(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+        self.joined(py, name)
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        HeaderName::from_bytes(name.as_bytes()).is_ok_and(|name| self.map.contains_key(name))
+    }
+
+    fn __len__(&self) -> usize {
+        self.map.keys_len()
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        PyList::new(py, self.keys())?.try_iter()
+    }
+
+    /// Equal to other `Headers` with the same values, or to a mapping with the same joined values,
+    /// names compared case-insensitively.
+    fn __eq__(&self, py: Python<'_>, other: &Bound<'_, PyAny>) -> PyResult<bool> {
+        if let Ok(other) = other.downcast::<Headers>() {
+            return Ok(self.map == other.get().map);
+        }
+        let Ok(other) = other.extract::<IndexMap<String, Bound<'_, PyAny>, RandomState>>() else {
+            return Ok(false);
+        };
+        if other.len() != self.__len__() {
+            return Ok(false);
+        }
+        for (name, value) in other {
+            match self.joined(py, &name) {
+                Some(own) if own.bind(py).eq(value)? => {}
+                _ => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        let items = self.items(py).into_pyobject(py)?;
+        Ok(format!("Headers({})", items.repr()?))
+    }
+}
+
+fn value_to_py(py: Python<'_>, value: &HeaderValue) -> PyObject {
+    bytes_to_py(py, value.as_bytes())
+}
+
+/// A header value as str, or as bytes if it is not valid UTF-8.
+fn bytes_to_py(py: Python<'_>, bytes: &[u8]) -> PyObject {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => PyString::new(py, s).into_any().unbind(),
+        Err(_) => PyBytes::new(py, bytes).into_any().unbind(),
+    }
+}
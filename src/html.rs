@@ -0,0 +1,533 @@
+use std::ops::Range;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+use anyhow::Result;
+use ego_tree::iter::Edge;
+use foldhash::fast::RandomState;
+use indexmap::IndexMap;
+use pyo3::prelude::*;
+use pyo3::IntoPyObjectExt;
+
+use crate::css::SelectorList;
+use crate::xpath::{SxdCopy, XNode, XPath, XValue};
+
+/// Parsed HTML document, stored as an arena of nodes in document order.
+///
+/// The descendants of a node are the nodes following it, up to the end of its subtree.
+/// Unlike the DOM of `scraper`, it is `Send` and `Sync`, so that it can be cached on a
+/// `Response` and shared by its `Element`s.
+#[derive(Debug)]
+pub struct Document {
+    nodes: Vec<Node>,
+    // Copy evaluated by `sxd-xpath`, built by the first XPath query
+    sxd: OnceLock<Mutex<SxdCopy>>,
+}
+
+#[derive(Debug)]
+pub struct Node {
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    // Index among the children of the parent
+    pub index: usize,
+    // End of the subtree, exclusive
+    end: usize,
+    pub data: NodeData,
+}
+
+#[derive(Debug)]
+pub enum NodeData {
+    Document,
+    Element {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    Text(String),
+    Comment(String),
+}
+
+impl Document {
+    /// Parses an HTML document, the way browsers do: invalid markup is fixed up, not rejected.
+    ///
+    /// The tree built by `scraper` is laid out in document order, without the doctype and the
+    /// processing instructions. The contents of a `<template>` are kept as its children, to be
+    /// searchable.
+    pub fn parse(html: &str) -> Self {
+        let html = scraper::Html::parse_document(html);
+        let mut nodes: Vec<Node> = Vec::new();
+        // Open nodes of the arena, the innermost last
+        let mut open: Vec<usize> = Vec::new();
+        for edge in html.tree.root().traverse() {
+            let (node, opening) = match edge {
+                Edge::Open(node) => (node, true),
+                Edge::Close(node) => (node, false),
+            };
+            let data = match node.value() {
+                // The template contents, whose children go to the template
+                scraper::Node::Fragment
+                | scraper::Node::Doctype(_)
+                | scraper::Node::ProcessingInstruction(_) => continue,
+                _ if !opening => {
+                    let id = open.pop().expect("closing a node that is not open");
+                    nodes[id].end = nodes.len();
+                    continue;
+                }
+                scraper::Node::Document => NodeData::Document,
+                scraper::Node::Element(element) => NodeData::Element {
+                    name: element.name().to_string(),
+                    attrs: element
+                        .attrs
+                        .iter()
+                        .map(|(name, value)| {
+                            let name = match &name.prefix {
+                                Some(prefix) => format!("{}:{}", prefix, name.local),
+                                None => name.local.to_string(),
+                            };
+                            (name, value.to_string())
+                        })
+                        .collect(),
+                },
+                scraper::Node::Text(text) => NodeData::Text(text.text.to_string()),
+                scraper::Node::Comment(comment) => NodeData::Comment(comment.comment.to_string()),
+            };
+            let id = nodes.len();
+            let parent = open.last().copied();
+            let mut index = 0;
+            if let Some(parent) = parent {
+                let children = &mut nodes[parent].children;
+                index = children.len();
+                children.push(id);
+            }
+            nodes.push(Node {
+                parent,
+                children: Vec::new(),
+                index,
+                end: id + 1,
+                data,
+            });
+            open.push(id);
+        }
+        Document {
+            nodes,
+            sxd: OnceLock::new(),
+        }
+    }
+
+    pub const ROOT: usize = 0;
+
+    pub fn node(&self, id: usize) -> &Node {
+        &self.nodes[id]
+    }
+
+    /// Tag name of an element.
+    pub fn name(&self, id: usize) -> Option<&str> {
+        match &self.nodes[id].data {
+            NodeData::Element { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn is_element(&self, id: usize) -> bool {
+        matches!(self.nodes[id].data, NodeData::Element { .. })
+    }
+
+    pub fn attrs(&self, id: usize) -> &[(String, String)] {
+        match &self.nodes[id].data {
+            NodeData::Element { attrs, .. } => attrs,
+            _ => &[],
+        }
+    }
+
+    pub fn attr(&self, id: usize, name: &str) -> Option<&str> {
+        self.attrs(id)
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The copy of the document for `sxd-xpath`, built on the first call.
+    ///
+    /// XPath queries on the document are serialized, as the copy can't be shared between threads.
+    pub fn sxd_copy(&self) -> MutexGuard<'_, SxdCopy> {
+        let sxd = self.sxd.get_or_init(|| Mutex::new(SxdCopy::new(self)));
+        sxd.lock().unwrap()
+    }
+
+    /// Ids of the descendants of a node.
+    pub fn descendants(&self, id: usize) -> Range<usize> {
+        id + 1..self.nodes[id].end
+    }
+
+    /// Ids of the nodes following a node in document order, its descendants excluded.
+    pub fn following(&self, id: usize) -> Range<usize> {
+        self.nodes[id].end..self.nodes.len()
+    }
+
+    /// The element children of a node.
+    pub fn element_children(&self, id: usize) -> impl Iterator<Item = usize> + '_ {
+        self.nodes[id]
+            .children
+            .iter()
+            .copied()
+            .filter(|&child| self.is_element(child))
+    }
+
+    /// The children of the parent of a node, the node included.
+    pub fn siblings(&self, id: usize) -> &[usize] {
+        match self.nodes[id].parent {
+            Some(parent) => &self.nodes[parent].children,
+            None => &[],
+        }
+    }
+
+    /// The text of a node: the concatenated text of its descendants for an element.
+    pub fn text(&self, id: usize) -> String {
+        match &self.nodes[id].data {
+            NodeData::Text(text) | NodeData::Comment(text) => text.clone(),
+            NodeData::Document | NodeData::Element { .. } => self
+                .descendants(id)
+                .filter_map(|i| match &self.nodes[i].data {
+                    NodeData::Text(text) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// The HTML of a node, the node included.
+    pub fn outer_html(&self, id: usize) -> String {
+        self.outer_html_filtered(id, &|_| true)
+    }
+
+    /// The HTML of a node, leaving out the descendants for which `keep` is false.
+    pub fn outer_html_filtered(&self, id: usize, keep: &dyn Fn(usize) -> bool) -> String {
+        let mut html = String::new();
+        self.serialize(id, keep, &mut html);
+        html
+    }
+
+    /// The HTML of the children of a node.
+    pub fn inner_html(&self, id: usize) -> String {
+        let mut html = String::new();
+        for &child in &self.nodes[id].children {
+            self.serialize(child, &|_| true, &mut html);
+        }
+        html
+    }
+
+    fn start_tag(&self, name: &str, attrs: &[(String, String)], html: &mut String) {
+        html.push('<');
+        html.push_str(name);
+        for (name, value) in attrs {
+            html.push(' ');
+            html.push_str(name);
+            html.push_str("=\"");
+            escape(value, true, html);
+            html.push('"');
+        }
+        html.push('>');
+    }
+
+    /// Serializes a node with an explicit stack, so that deeply nested documents don't overflow
+    /// the thread stack.
+    fn serialize(&self, id: usize, keep: &dyn Fn(usize) -> bool, html: &mut String) {
+        let mut stack = vec![Step::Open(id)];
+        while let Some(step) = stack.pop() {
+            let id = match step {
+                Step::Open(id) => id,
+                Step::Close(name) => {
+                    html.push_str("</");
+                    html.push_str(name);
+                    html.push('>');
+                    continue;
+                }
+            };
+            // Pushed in reverse, to be popped in document order
+            let children = self.nodes[id]
+                .children
+                .iter()
+                .rev()
+                .filter(|&&child| keep(child))
+                .map(|&child| Step::Open(child));
+            match &self.nodes[id].data {
+                NodeData::Document => stack.extend(children),
+                NodeData::Element { name, attrs } => {
+                    self.start_tag(name, attrs, html);
+                    if VOID_ELEMENTS.contains(&name.as_str()) {
+                        continue;
+                    }
+                    stack.push(Step::Close(name));
+                    stack.extend(children);
+                }
+                NodeData::Text(text) => {
+                    let raw = self.nodes[id]
+                        .parent
+                        .and_then(|parent| self.name(parent))
+                        .is_some_and(|parent| RAW_TEXT_ELEMENTS.contains(&parent));
+                    match raw {
+                        true => html.push_str(text),
+                        false => escape(text, false, html),
+                    }
+                }
+                NodeData::Comment(text) => {
+                    html.push_str("<!--");
+                    html.push_str(text);
+                    html.push_str("-->");
+                }
+            }
+        }
+    }
+}
+
+/// Serialization step: a node to open, or the end tag of an element.
+enum Step<'a> {
+    Open(usize),
+    Close(&'a str),
+}
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "basefont", "bgsound", "br", "col", "embed", "frame", "hr", "img", "input",
+    "keygen", "link", "meta", "param", "source", "track", "wbr",
+];
+
+// Elements whose text is not escaped
+const RAW_TEXT_ELEMENTS: &[&str] = &[
+    "iframe",
+    "noembed",
+    "noframes",
+    "noscript",
+    "plaintext",
+    "script",
+    "style",
+    "xmp",
+];
+
+/// Escapes text the way the HTML serialization algorithm does.
+fn escape(text: &str, attribute: bool, html: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '\u{a0}' => html.push_str("&nbsp;"),
+            '"' if attribute => html.push_str("&quot;"),
+            '<' if !attribute => html.push_str("&lt;"),
+            '>' if !attribute => html.push_str("&gt;"),
+            c => html.push(c),
+        }
+    }
+}
+
+/// An element of an HTML document.
+#[pyclass(frozen)]
+#[derive(Clone)]
+pub struct Element {
+    doc: Arc<Document>,
+    id: usize,
+}
+
+#[pymethods]
+impl Element {
+    /// The tag name, lowercase for HTML elements.
+    #[getter]
+    fn tag(&self) -> &str {
+        self.doc.name(self.id).unwrap_or_default()
+    }
+
+    /// The text of the element and its descendants.
+    #[getter]
+    fn text(&self) -> String {
+        self.doc.text(self.id)
+    }
+
+    #[getter]
+    fn attrs(&self) -> IndexMap<String, String, RandomState> {
+        self.doc.attrs(self.id).iter().cloned().collect()
+    }
+
+    /// The HTML of the element, the element included.
+    #[getter]
+    fn html(&self) -> String {
+        self.doc.outer_html(self.id)
+    }
+
+    /// The HTML of the children of the element.
+    #[getter]
+    fn inner_html(&self) -> String {
+        self.doc.inner_html(self.id)
+    }
+
+    /// The value of the attribute `name`, or `default` if it is missing.
+    #[pyo3(signature = (name, default=None))]
+    fn get(&self, name: &str, default: Option<String>) -> Option<String> {
+        self.doc.attr(self.id, name).map(str::to_string).or(default)
+    }
+
+    /// The descendants of the element matching the CSS selector, in document order.
+    fn select(&self, css: &str) -> Result<Vec<Element>> {
+        select(&self.doc, self.id, css)
+    }
+
+    /// The first descendant of the element matching the CSS selector, or None.
+    fn select_one(&self, css: &str) -> Result<Option<Element>> {
+        select_one(&self.doc, self.id, css)
+    }
+
+    /// Evaluates an XPath 1.0 expression, with the element as the context node.
+    fn xpath(&self, py: Python, expr: &str) -> Result<PyObject> {
+        xpath(py, &self.doc, self.id, expr)
+    }
+
+    fn __repr__(&self) -> String {
+        let mut start_tag = String::new();
+        self.doc
+            .start_tag(self.tag(), self.doc.attrs(self.id), &mut start_tag);
+        format!("Element({})", start_tag)
+    }
+}
+
+/// The descendants of `scope` matching the CSS selector `css`.
+pub fn select(doc: &Arc<Document>, scope: usize, css: &str) -> Result<Vec<Element>> {
+    let selector = SelectorList::parse(css)?;
+    Ok(selector
+        .select(doc, scope)
+        .map(|id| Element {
+            doc: Arc::clone(doc),
+            id,
+        })
+        .collect())
+}
+
+pub fn select_one(doc: &Arc<Document>, scope: usize, css: &str) -> Result<Option<Element>> {
+    let selector = SelectorList::parse(css)?;
+    let first = selector.select(doc, scope).next();
+    Ok(first.map(|id| Element {
+        doc: Arc::clone(doc),
+        id,
+    }))
+}
+
+/// Evaluates the XPath expression `expr` with the node `context`.
+///
+/// Node-sets are returned as lists, of `Element` for elements and of `str` for the other nodes,
+/// like lxml does. Other results are returned as `str`, `float` or `bool`.
+pub fn xpath(py: Python, doc: &Arc<Document>, context: usize, expr: &str) -> Result<PyObject> {
+    let value = XPath::parse(expr)?.evaluate(doc, context)?;
+    let value = match value {
+        XValue::Nodes(nodes) => {
+            let items = nodes
+                .into_iter()
+                .filter_map(|node| match node {
+                    XNode::Node(id) => match &doc.node(id).data {
+                        NodeData::Element { .. } => Some(
+                            Element {
+                                doc: Arc::clone(doc),
+                                id,
+                            }
+                            .into_py_any(py),
+                        ),
+                        NodeData::Text(text) | NodeData::Comment(text) => {
+                            Some(text.into_py_any(py))
+                        }
+                        NodeData::Document => None,
+                    },
+                    XNode::Attr(id, index) => Some((&doc.attrs(id)[index].1).into_py_any(py)),
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            items.into_py_any(py)?
+        }
+        XValue::String(s) => s.into_py_any(py)?,
+        XValue::Number(n) => n.into_py_any(py)?,
+        XValue::Boolean(b) => b.into_py_any(py)?,
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod html_tests {
+    use super::*;
+
+    const HTML: &str = r#"<!DOCTYPE html>
+<html><head><title>Title</title></head>
+<body>
+<p id="first" class="intro">Hello <b>world</b><br>!</p>
+<p>a &lt; b &amp; c</p>
+<script>if (a < b) {}</script>
+</body></html>"#;
+
+    #[test]
+    fn test_parse_tree() {
+        let doc = Document::parse(HTML);
+        assert!(matches!(doc.node(Document::ROOT).data, NodeData::Document));
+        let html = doc.element_children(Document::ROOT).next().unwrap();
+        assert_eq!(doc.name(html), Some("html"));
+        let names: Vec<_> = doc
+            .element_children(html)
+            .filter_map(|id| doc.name(id))
+            .collect();
+        assert_eq!(names, vec!["head", "body"]);
+        for id in doc.descendants(html) {
+            let parent = doc.node(id).parent.unwrap();
+            assert!(doc.descendants(parent).contains(&id));
+            assert_eq!(doc.siblings(id)[doc.node(id).index], id);
+        }
+    }
+
+    #[test]
+    fn test_text_and_attrs() {
+        let doc = Document::parse(HTML);
+        let p = doc
+            .descendants(Document::ROOT)
+            .find(|&id| doc.attr(id, "id") == Some("first"))
+            .unwrap();
+        assert_eq!(doc.text(p), "Hello world!");
+        assert_eq!(doc.attr(p, "class"), Some("intro"));
+        assert_eq!(doc.attr(p, "missing"), None);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let doc = Document::parse(HTML);
+        let ps: Vec<_> = doc
+            .descendants(Document::ROOT)
+            .filter(|&id| doc.name(id) == Some("p"))
+            .collect();
+        assert_eq!(
+            doc.outer_html(ps[0]),
+            r#"<p id="first" class="intro">Hello <b>world</b><br>!</p>"#
+        );
+        assert_eq!(doc.inner_html(ps[1]), "a &lt; b &amp; c");
+        let script = doc
+            .descendants(Document::ROOT)
+            .find(|&id| doc.name(id) == Some("script"))
+            .unwrap();
+        assert_eq!(doc.inner_html(script), "if (a < b) {}");
+    }
+
+    #[test]
+    fn test_serialize_deep_nesting() {
+        // Built directly: the parser's scope checks are quadratic in the nesting depth
+        let depth = 100_000;
+        let len = depth + 2;
+        let nodes = (0..len)
+            .map(|id: usize| Node {
+                parent: id.checked_sub(1),
+                children: if id + 1 < len { vec![id + 1] } else { vec![] },
+                index: 0,
+                end: len,
+                data: match id {
+                    0 => NodeData::Document,
+                    id if id == len - 1 => NodeData::Text("x".to_string()),
+                    _ => NodeData::Element {
+                        name: "div".to_string(),
+                        attrs: vec![],
+                    },
+                },
+            })
+            .collect();
+        let doc = Document {
+            nodes,
+            sxd: OnceLock::new(),
+        };
+        let html = format!("{}x{}", "<div>".repeat(depth), "</div>".repeat(depth));
+        assert_eq!(doc.outer_html(Document::ROOT), html);
+        assert_eq!(doc.inner_html(1), html[5..html.len() - 6]);
+    }
+}
@@ -0,0 +1,844 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::Result;
+use foldhash::fast::RandomState;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyInt, PyList, PyString};
+use pyo3::IntoPyObjectExt;
+
+// Maximum nesting of arrays and objects
+const MAX_DEPTH: usize = 512;
+
+/// Options of `Response.json`.
+#[derive(Clone, Copy, Debug)]
+pub struct JsonOptions {
+    /// Integers that don't fit in 64 bits become `int` instead of `float`.
+    pub big_int: bool,
+    /// Numbers with a fraction or an exponent become `decimal.Decimal` instead of `float`.
+    pub decimal: bool,
+    /// Rejects what Python's `json` module accepts beyond the JSON spec: control characters
+    /// and lone surrogates in strings, `NaN`, `Infinity` and `-Infinity`.
+    pub strict: bool,
+}
+
+/// A JSON number, classified according to the options.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number<'a> {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    /// Integer literal too big for 64 bits.
+    BigInt(&'a str),
+    /// Literal of a number with a fraction or an exponent.
+    Decimal(&'a str),
+}
+
+/// A JSON string. Outside of strict mode, a string can hold lone surrogates, which `str` can't:
+/// such strings are kept as UTF-16, and built with the surrogates like Python's `json` does.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonStr<'a> {
+    Str(Cow<'a, str>),
+    Utf16(Vec<u16>),
+}
+
+impl<'a> From<&'a str> for JsonStr<'a> {
+    fn from(value: &'a str) -> Self {
+        JsonStr::Str(Cow::Borrowed(value))
+    }
+}
+
+impl PartialEq<&str> for JsonStr<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        match self {
+            JsonStr::Str(s) => s == other,
+            JsonStr::Utf16(units) => units.iter().copied().eq(other.encode_utf16()),
+        }
+    }
+}
+
+/// Receives the values as they are parsed, innermost first. Object entries are inserted one
+/// by one, between `object` and `end_object`.
+pub trait Sink<'a> {
+    type Value;
+    type Object;
+
+    fn null(&mut self) -> Result<Self::Value>;
+    fn bool(&mut self, value: bool) -> Result<Self::Value>;
+    fn number(&mut self, value: Number<'a>) -> Result<Self::Value>;
+    fn string(&mut self, value: JsonStr<'a>) -> Result<Self::Value>;
+    fn array(&mut self, items: Vec<Self::Value>) -> Result<Self::Value>;
+    fn object(&mut self) -> Self::Object;
+    fn insert(
+        &mut self,
+        object: &mut Self::Object,
+        key: JsonStr<'a>,
+        value: Self::Value,
+    ) -> Result<()>;
+    fn end_object(&mut self, object: Self::Object) -> Result<Self::Value>;
+}
+
+// Keys longer than this are not cached
+const MAX_CACHED_KEY_LEN: usize = 64;
+
+/// Builds Python objects, holding the GIL.
+pub struct PySink<'py> {
+    py: Python<'py>,
+    decimal: Option<Bound<'py, PyAny>>,
+    // Object keys already built, reused like the memo of Python's `json` module: the
+    // objects of an array usually share their keys, whose hash is then computed once
+    keys: HashMap<Box<str>, Bound<'py, PyString>, RandomState>,
+}
+
+impl<'py> PySink<'py> {
+    pub fn new(py: Python<'py>, options: JsonOptions) -> PyResult<Self> {
+        let decimal = match options.decimal {
+            true => Some(py.import("decimal")?.getattr("Decimal")?),
+            false => None,
+        };
+        Ok(PySink {
+            py,
+            decimal,
+            keys: HashMap::default(),
+        })
+    }
+
+    fn key(&mut self, key: JsonStr) -> PyResult<Bound<'py, PyAny>> {
+        let key = match key {
+            JsonStr::Str(key) if key.len() <= MAX_CACHED_KEY_LEN => key,
+            key => return self.str(key),
+        };
+        if let Some(cached) = self.keys.get(&*key) {
+            return Ok(cached.clone().into_any());
+        }
+        let value = PyString::new(self.py, &key);
+        self.keys.insert(key.into(), value.clone());
+        Ok(value.into_any())
+    }
+
+    fn str(&self, value: JsonStr) -> PyResult<Bound<'py, PyAny>> {
+        match value {
+            JsonStr::Str(value) => Ok(PyString::new(self.py, &value).into_any()),
+            JsonStr::Utf16(units) => {
+                let bytes: Vec<u8> = units.iter().flat_map(|unit| unit.to_le_bytes()).collect();
+                PyBytes::new(self.py, &bytes).call_method1("decode", ("utf-16-le", "surrogatepass"))
+            }
+        }
+    }
+}
+
+impl<'a, 'py> Sink<'a> for PySink<'py> {
+    type Value = Bound<'py, PyAny>;
+    type Object = Bound<'py, PyDict>;
+
+    fn null(&mut self) -> Result<Self::Value> {
+        Ok(self.py.None().into_bound(self.py))
+    }
+
+    fn bool(&mut self, value: bool) -> Result<Self::Value> {
+        Ok(value.into_bound_py_any(self.py)?)
+    }
+
+    fn number(&mut self, value: Number<'a>) -> Result<Self::Value> {
+        let number = match value {
+            Number::Int(n) => n.into_bound_py_any(self.py)?,
+            Number::UInt(n) => n.into_bound_py_any(self.py)?,
+            Number::Float(n) => n.into_bound_py_any(self.py)?,
+            Number::BigInt(literal) => self.py.get_type::<PyInt>().call1((literal,))?,
+            Number::Decimal(literal) => match &self.decimal {
+                Some(decimal) => decimal.call1((literal,))?,
+                None => literal.parse::<f64>()?.into_bound_py_any(self.py)?,
+            },
+        };
+        Ok(number)
+    }
+
+    fn string(&mut self, value: JsonStr<'a>) -> Result<Self::Value> {
+        Ok(self.str(value)?)
+    }
+
+    fn array(&mut self, items: Vec<Self::Value>) -> Result<Self::Value> {
+        Ok(PyList::new(self.py, items)?.into_any())
+    }
+
+    fn object(&mut self) -> Self::Object {
+        PyDict::new(self.py)
+    }
+
+    fn insert(
+        &mut self,
+        object: &mut Self::Object,
+        key: JsonStr<'a>,
+        value: Self::Value,
+    ) -> Result<()> {
+        Ok(object.set_item(self.key(key)?, value)?)
+    }
+
+    fn end_object(&mut self, object: Self::Object) -> Result<Self::Value> {
+        Ok(object.into_any())
+    }
+}
+
+/// Compact parsed document, borrowing the unescaped strings from the input.
+///
+/// Parsed without the GIL, then converted to Python objects.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node<'a> {
+    Null,
+    Bool(bool),
+    Number(Number<'a>),
+    String(JsonStr<'a>),
+    Array(Vec<Node<'a>>),
+    Object(Vec<(JsonStr<'a>, Node<'a>)>),
+}
+
+impl<'a> Node<'a> {
+    /// Feeds the document to `sink`.
+    pub fn build<S: Sink<'a>>(self, sink: &mut S) -> Result<S::Value> {
+        match self {
+            Node::Null => sink.null(),
+            Node::Bool(value) => sink.bool(value),
+            Node::Number(value) => sink.number(value),
+            Node::String(value) => sink.string(value),
+            Node::Array(items) => {
+                let items = items
+                    .into_iter()
+                    .map(|item| item.build(sink))
+                    .collect::<Result<Vec<_>>>()?;
+                sink.array(items)
+            }
+            Node::Object(entries) => {
+                let mut object = sink.object();
+                for (key, value) in entries {
+                    let value = value.build(sink)?;
+                    sink.insert(&mut object, key, value)?;
+                }
+                sink.end_object(object)
+            }
+        }
+    }
+}
+
+/// Builds a `Node` tree.
+pub struct NodeSink;
+
+impl<'a> Sink<'a> for NodeSink {
+    type Value = Node<'a>;
+    type Object = Vec<(JsonStr<'a>, Node<'a>)>;
+
+    fn null(&mut self) -> Result<Self::Value> {
+        Ok(Node::Null)
+    }
+
+    fn bool(&mut self, value: bool) -> Result<Self::Value> {
+        Ok(Node::Bool(value))
+    }
+
+    fn number(&mut self, value: Number<'a>) -> Result<Self::Value> {
+        Ok(Node::Number(value))
+    }
+
+    fn string(&mut self, value: JsonStr<'a>) -> Result<Self::Value> {
+        Ok(Node::String(value))
+    }
+
+    fn array(&mut self, items: Vec<Self::Value>) -> Result<Self::Value> {
+        Ok(Node::Array(items))
+    }
+
+    fn object(&mut self) -> Self::Object {
+        Vec::new()
+    }
+
+    fn insert(
+        &mut self,
+        object: &mut Self::Object,
+        key: JsonStr<'a>,
+        value: Self::Value,
+    ) -> Result<()> {
+        object.push((key, value));
+        Ok(())
+    }
+
+    fn end_object(&mut self, object: Self::Object) -> Result<Self::Value> {
+        Ok(Node::Object(object))
+    }
+}
+
+/// Decodes the JSON document `input` into Python objects.
+///
+/// With `release_gil`, the document is parsed into a `Node` tree without the GIL, so that other
+/// threads can run meanwhile, and then converted.
+pub fn loads(
+    py: Python<'_>,
+    input: &[u8],
+    options: JsonOptions,
+    release_gil: bool,
+) -> Result<PyObject> {
+    let mut sink = PySink::new(py, options)?;
+    let value = if release_gil {
+        let node = py.allow_threads(|| parse(input, options, &mut NodeSink))?;
+        node.build(&mut sink)?
+    } else {
+        parse(input, options, &mut sink)?
+    };
+    Ok(value.unbind())
+}
+
+/// Invalid JSON document.
+#[derive(Debug)]
+pub struct JsonError {
+    msg: &'static str,
+    line: usize,
+    column: usize,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {} column {}",
+            self.msg, self.line, self.column
+        )
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// Parses the JSON document `input`, feeding the values to `sink`.
+pub fn parse<'a, S: Sink<'a>>(
+    input: &'a [u8],
+    options: JsonOptions,
+    sink: &mut S,
+) -> Result<S::Value> {
+    let input = input.strip_prefix(b"\xef\xbb\xbf").unwrap_or(input);
+    let input = std::str::from_utf8(input)
+        .map_err(|e| Parser::error_at(input, e.valid_up_to(), "invalid UTF-8"))?;
+    let mut parser = Parser {
+        input,
+        bytes: input.as_bytes(),
+        pos: 0,
+        depth: 0,
+        options,
+    };
+    let value = parser.parse_value(sink)?;
+    parser.skip_whitespace();
+    if parser.pos < parser.bytes.len() {
+        return Err(parser.error("trailing characters").into());
+    }
+    Ok(value)
+}
+
+/// Unescaped string being built, switched to UTF-16 by the first lone surrogate.
+enum Unescaped {
+    Str(String),
+    Utf16(Vec<u16>),
+}
+
+impl Unescaped {
+    fn push_str(&mut self, value: &str) {
+        match self {
+            Unescaped::Str(s) => s.push_str(value),
+            Unescaped::Utf16(units) => units.extend(value.encode_utf16()),
+        }
+    }
+
+    fn push_char(&mut self, c: char) {
+        match self {
+            Unescaped::Str(s) => s.push(c),
+            Unescaped::Utf16(units) => units.extend_from_slice(c.encode_utf16(&mut [0; 2])),
+        }
+    }
+
+    fn push_surrogate(&mut self, surrogate: u16) {
+        if let Unescaped::Str(s) = self {
+            *self = Unescaped::Utf16(s.encode_utf16().collect());
+        }
+        if let Unescaped::Utf16(units) = self {
+            units.push(surrogate);
+        }
+    }
+
+    fn into_string<'a>(self) -> JsonStr<'a> {
+        match self {
+            Unescaped::Str(s) => JsonStr::Str(Cow::Owned(s)),
+            Unescaped::Utf16(units) => JsonStr::Utf16(units),
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+    options: JsonOptions,
+}
+
+impl<'a> Parser<'a> {
+    fn error_at(bytes: &[u8], pos: usize, msg: &'static str) -> JsonError {
+        let before = &bytes[..pos.min(bytes.len())];
+        let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+        let line_start = before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        JsonError {
+            msg,
+            line,
+            column: pos - line_start + 1,
+        }
+    }
+
+    fn error(&self, msg: &'static str) -> JsonError {
+        Self::error_at(self.bytes, self.pos, msg)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes `literal`, which starts at the current position.
+    fn expect_literal(&mut self, literal: &str) -> Result<(), JsonError> {
+        if !self.input[self.pos..].starts_with(literal) {
+            return Err(self.error("expected value"));
+        }
+        self.pos += literal.len();
+        Ok(())
+    }
+
+    /// `NaN`, `Infinity` and `-Infinity`, outside of strict mode.
+    fn parse_constant(&mut self, literal: &str, value: f64) -> Result<Number<'a>, JsonError> {
+        if self.options.strict {
+            return Err(self.error("expected value"));
+        }
+        self.expect_literal(literal)?;
+        Ok(Number::Float(value))
+    }
+
+    fn parse_value<S: Sink<'a>>(&mut self, sink: &mut S) -> Result<S::Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.parse_object(sink),
+            Some(b'[') => self.parse_array(sink),
+            Some(b'"') => {
+                let value = self.parse_string()?;
+                sink.string(value)
+            }
+            Some(b't') => {
+                self.expect_literal("true")?;
+                sink.bool(true)
+            }
+            Some(b'f') => {
+                self.expect_literal("false")?;
+                sink.bool(false)
+            }
+            Some(b'n') => {
+                self.expect_literal("null")?;
+                sink.null()
+            }
+            Some(b'N') => {
+                let value = self.parse_constant("NaN", f64::NAN)?;
+                sink.number(value)
+            }
+            Some(b'I') => {
+                let value = self.parse_constant("Infinity", f64::INFINITY)?;
+                sink.number(value)
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                let value = self.parse_number()?;
+                sink.number(value)
+            }
+            Some(_) => Err(self.error("expected value").into()),
+            None => Err(self.error("EOF while parsing a value").into()),
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), JsonError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("recursion limit exceeded"));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Consumes the `,` between two items, or the `close` delimiter. Returns true at the end.
+    fn next_item(&mut self, close: u8) -> Result<bool, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b',') => {
+                self.pos += 1;
+                Ok(false)
+            }
+            Some(b) if b == close => {
+                self.pos += 1;
+                self.depth -= 1;
+                Ok(true)
+            }
+            Some(_) if close == b']' => Err(self.error("expected `,` or `]`")),
+            Some(_) => Err(self.error("expected `,` or `}`")),
+            None => Err(self.error("EOF while parsing a list or an object")),
+        }
+    }
+
+    fn parse_array<S: Sink<'a>>(&mut self, sink: &mut S) -> Result<S::Value> {
+        self.enter()?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            self.depth -= 1;
+            return sink.array(items);
+        }
+        loop {
+            items.push(self.parse_value(sink)?);
+            if self.next_item(b']')? {
+                return sink.array(items);
+            }
+        }
+    }
+
+    fn parse_object<S: Sink<'a>>(&mut self, sink: &mut S) -> Result<S::Value> {
+        self.enter()?;
+        let mut object = sink.object();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            self.depth -= 1;
+            return sink.end_object(object);
+        }
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b'"') => {}
+                Some(_) => return Err(self.error("key must be a string").into()),
+                None => return Err(self.error("EOF while parsing an object").into()),
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            if self.peek() != Some(b':') {
+                return Err(self.error("expected `:`").into());
+            }
+            self.pos += 1;
+            let value = self.parse_value(sink)?;
+            sink.insert(&mut object, key, value)?;
+            if self.next_item(b'}')? {
+                return sink.end_object(object);
+            }
+        }
+    }
+
+    fn skip_digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn parse_number(&mut self) -> Result<Number<'a>, JsonError> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+            if self.peek() == Some(b'I') {
+                return self.parse_constant("Infinity", f64::NEG_INFINITY);
+            }
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => {
+                self.skip_digits();
+            }
+            _ => return Err(self.error("invalid number")),
+        }
+        let mut is_float = false;
+        if self.peek() == Some(b'.') {
+            is_float = true;
+            self.pos += 1;
+            if self.skip_digits() == 0 {
+                return Err(self.error("invalid number"));
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            is_float = true;
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if self.skip_digits() == 0 {
+                return Err(self.error("invalid number"));
+            }
+        }
+
+        let literal = &self.input[start..self.pos];
+        let number = if is_float {
+            match self.options.decimal {
+                true => Number::Decimal(literal),
+                false => Number::Float(literal.parse().map_err(|_| self.error("invalid number"))?),
+            }
+        } else if let Ok(n) = literal.parse::<i64>() {
+            Number::Int(n)
+        } else if let Ok(n) = literal.parse::<u64>() {
+            Number::UInt(n)
+        } else if self.options.big_int {
+            Number::BigInt(literal)
+        } else {
+            Number::Float(literal.parse().map_err(|_| self.error("invalid number"))?)
+        };
+        Ok(number)
+    }
+
+    /// Parses a string, borrowing it from the input unless it has escapes.
+    fn parse_string(&mut self) -> Result<JsonStr<'a>, JsonError> {
+        self.pos += 1;
+        let mut start = self.pos;
+        let mut unescaped: Option<Unescaped> = None;
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    let tail = &self.input[start..self.pos];
+                    self.pos += 1;
+                    return Ok(match unescaped {
+                        Some(mut s) => {
+                            s.push_str(tail);
+                            s.into_string()
+                        }
+                        None => tail.into(),
+                    });
+                }
+                Some(b'\\') => {
+                    let s = unescaped.get_or_insert_with(|| Unescaped::Str(String::new()));
+                    s.push_str(&self.input[start..self.pos]);
+                    self.pos += 1;
+                    match self.parse_escape()? {
+                        Ok(c) => s.push_char(c),
+                        Err(surrogate) => s.push_surrogate(surrogate),
+                    }
+                    start = self.pos;
+                }
+                Some(b) if b < 0x20 && self.options.strict => {
+                    return Err(self.error("control character in string"));
+                }
+                Some(_) => self.pos += 1,
+                None => return Err(self.error("EOF while parsing a string")),
+            }
+        }
+    }
+
+    /// Parses the escape sequence after a backslash: a character, or a lone surrogate outside
+    /// of strict mode.
+    fn parse_escape(&mut self) -> Result<Result<char, u16>, JsonError> {
+        let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.pos += 1;
+                return self.parse_unicode_escape();
+            }
+            Some(_) => return Err(self.error("invalid escape")),
+            None => return Err(self.error("EOF while parsing a string")),
+        };
+        self.pos += 1;
+        Ok(Ok(c))
+    }
+
+    /// Parses the `XXXX` of a `\uXXXX` escape, combining surrogate pairs.
+    fn parse_unicode_escape(&mut self) -> Result<Result<char, u16>, JsonError> {
+        let first = self.parse_hex4()?;
+        let code = match first {
+            0xD800..=0xDBFF if self.input[self.pos..].starts_with("\\u") => {
+                let pos = self.pos;
+                self.pos += 2;
+                match self.parse_hex4()? {
+                    second @ 0xDC00..=0xDFFF => {
+                        0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+                    }
+                    _ => {
+                        // Not a pair: the second escape is parsed on its own
+                        self.pos = pos;
+                        first
+                    }
+                }
+            }
+            _ => first,
+        };
+        match char::from_u32(code) {
+            Some(c) => Ok(Ok(c)),
+            None if self.options.strict => Err(self.error("lone surrogate in string")),
+            // Only surrogates are not chars, and they fit in 16 bits
+            None => Ok(Err(code as u16)),
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let hex = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(hex, 16).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod json_tests {
+    use super::*;
+
+    const STRICT: JsonOptions = JsonOptions {
+        big_int: false,
+        decimal: false,
+        strict: true,
+    };
+
+    fn parse_node(input: &str, options: JsonOptions) -> Result<Node<'_>> {
+        parse(input.as_bytes(), options, &mut NodeSink)
+    }
+
+    #[test]
+    fn test_parse_document() {
+        let node = parse_node(
+            r#" {"a": [1, -2.5, true, null], "b": {"c": "d"}, "": []} "#,
+            STRICT,
+        )
+        .unwrap();
+        assert_eq!(
+            node,
+            Node::Object(vec![
+                (
+                    "a".into(),
+                    Node::Array(vec![
+                        Node::Number(Number::Int(1)),
+                        Node::Number(Number::Float(-2.5)),
+                        Node::Bool(true),
+                        Node::Null,
+                    ])
+                ),
+                (
+                    "b".into(),
+                    Node::Object(vec![("c".into(), Node::String("d".into()))])
+                ),
+                ("".into(), Node::Array(vec![])),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_numbers() {
+        let number = |input, options| match parse_node(input, options).unwrap() {
+            Node::Number(n) => n,
+            node => panic!("not a number: {:?}", node),
+        };
+        assert_eq!(number("0", STRICT), Number::Int(0));
+        assert_eq!(number("-0", STRICT), Number::Int(0));
+        assert_eq!(
+            number("18446744073709551615", STRICT),
+            Number::UInt(u64::MAX)
+        );
+        assert_eq!(number("1e2", STRICT), Number::Float(100.0));
+        assert_eq!(
+            number("123456789012345678901234567890", STRICT),
+            Number::Float(1.2345678901234568e29)
+        );
+
+        let options = JsonOptions {
+            big_int: true,
+            decimal: true,
+            ..STRICT
+        };
+        assert_eq!(
+            number("-123456789012345678901234567890", options),
+            Number::BigInt("-123456789012345678901234567890")
+        );
+        assert_eq!(number("0.10", options), Number::Decimal("0.10"));
+        assert_eq!(number("1E-7", options), Number::Decimal("1E-7"));
+
+        for invalid in ["01", "1.", ".5", "-", "1e", "+1", "0x10"] {
+            assert!(parse_node(invalid, STRICT).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_strings() {
+        let string = |input, options| match parse_node(input, options).unwrap() {
+            Node::String(s) => s,
+            node => panic!("not a string: {:?}", node),
+        };
+        assert!(matches!(
+            string(r#""plain""#, STRICT),
+            JsonStr::Str(Cow::Borrowed("plain"))
+        ));
+        assert_eq!(string(r#""a\"b\\c\/\n\t""#, STRICT), "a\"b\\c/\n\t");
+        assert_eq!(string(r#""é😀""#, STRICT), "é😀");
+        assert_eq!(string("\"caf\u{e9}\"", STRICT), "café");
+
+        assert!(parse_node(r#""\ud800""#, STRICT).is_err());
+        assert!(parse_node("\"a\tb\"", STRICT).is_err());
+        assert!(parse_node(r#""\x""#, STRICT).is_err());
+        assert!(parse_node(r#""open"#, STRICT).is_err());
+
+        let lenient = JsonOptions {
+            strict: false,
+            ..STRICT
+        };
+        // Lone surrogates are kept, like Python's `json` does
+        assert_eq!(
+            string(r#""a\u00e9\ud800x""#, lenient),
+            JsonStr::Utf16(vec![0x61, 0xe9, 0xd800, 0x78])
+        );
+        assert_eq!(
+            string(r#""\udc00\ud83d\ude00""#, lenient),
+            JsonStr::Utf16(vec![0xdc00, 0xd83d, 0xde00])
+        );
+        assert_eq!(string(r#""\ud83d\ude00""#, lenient), "😀");
+        assert_eq!(string("\"a\tb\"", lenient), "a\tb");
+    }
+
+    #[test]
+    fn test_parse_constants() {
+        assert!(parse_node("NaN", STRICT).is_err());
+        let lenient = JsonOptions {
+            strict: false,
+            ..STRICT
+        };
+        assert_eq!(
+            parse_node("[Infinity, -Infinity]", lenient).unwrap(),
+            Node::Array(vec![
+                Node::Number(Number::Float(f64::INFINITY)),
+                Node::Number(Number::Float(f64::NEG_INFINITY)),
+            ])
+        );
+        assert!(matches!(
+            parse_node("NaN", lenient).unwrap(),
+            Node::Number(Number::Float(n)) if n.is_nan()
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input: &str| parse_node(input, STRICT).unwrap_err().to_string();
+        assert_eq!(error(""), "EOF while parsing a value at line 1 column 1");
+        assert_eq!(error("[1,\n 2,]"), "expected value at line 2 column 4");
+        assert_eq!(error("{\"a\" 1}"), "expected `:` at line 1 column 6");
+        assert_eq!(error("{1: 2}"), "key must be a string at line 1 column 2");
+        assert_eq!(error("[1] x"), "trailing characters at line 1 column 5");
+        assert_eq!(error("tru"), "expected value at line 1 column 1");
+        assert!(error(&"[".repeat(MAX_DEPTH + 1)).starts_with("recursion limit exceeded"));
+        assert!(parse(b"\xef\xbb\xbf[\xff]", STRICT, &mut NodeSink).is_err());
+        assert!(parse(b"\xef\xbb\xbf[]", STRICT, &mut NodeSink).is_ok());
+    }
+}
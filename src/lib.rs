@@ -1,46 +1,100 @@
 #![allow(clippy::too_many_arguments)]
-use std::sync::{Arc, LazyLock, Mutex};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use anyhow::{Error, Result};
+use anyhow::{anyhow, Result};
 use bytes::Bytes;
 use foldhash::fast::RandomState;
 use indexmap::IndexMap;
 use pyo3::prelude::*;
-use pyo3::types::PyBytes;
+use pyo3::types::{PyBytes, PyDict};
+use pyo3_async_runtimes::tokio::future_into_py;
 use pythonize::depythonize;
 use rquest::{
-    header::{HeaderValue, COOKIE},
+    header::{HeaderValue, ACCEPT_ENCODING, COOKIE},
     multipart,
     redirect::Policy,
-    Body, Impersonate, ImpersonateOS, Method,
+    Body, Impersonate, ImpersonateOS, Method, RequestBuilder, Url,
 };
 use serde_json::Value;
-use tokio::{
-    fs::File,
-    runtime::{self, Runtime},
-};
+use tokio::fs::File;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
 use tokio_util::codec::{BytesCodec, FramedRead};
-use tracing;
+
+mod body;
+use body::content_to_body;
+
+mod cookies;
+use cookies::{Cookie, CookieJar};
+
+mod css;
+
+mod decoder;
+use decoder::{
+    accept_encoding_header, BodyDecoder, BodyLimits, BodyReader, DEFAULT_ACCEPT_ENCODING,
+};
+
+mod exceptions;
+use exceptions::request_error;
+
+mod headers;
+use headers::Headers;
+
+mod html;
+use html::Element;
+
+mod download;
+use download::{download_to_file, DownloadResult};
+
+mod json;
+
+mod limiter;
+use limiter::HostLimiter;
+
+mod links;
+use links::Link;
+
+mod markdown;
+
+mod metadata;
+use metadata::Metadata;
+
+mod readability;
+use readability::Article;
+
+mod redirect;
+use redirect::{send_following_redirects, RedirectResponse, Sent};
 
 mod response;
 use response::Response;
 
+mod retry;
+use retry::{send_with_retry, Retry, RetryArg};
+
+mod runtime;
+use runtime::{run_on, ClientRuntime};
+
+mod sse;
+use sse::{EventsIterator, ServerSentEvent};
+
+mod stream;
+use stream::{BytesIterator, LinesIterator, Replay, ResponseStream, TextIterator};
+
+mod timings;
+use timings::{with_connection_timings, GaiResolver, TimingLayer, TimingResolver, Timings};
+
 mod traits;
 use traits::{CookiesTraits, HeadersTraits, ImpersonateFromStr, ImpersonateOSFromStr};
 
 mod utils;
-use utils::load_ca_certs;
+use utils::{http_version_name, load_ca_certs};
 
-type IndexMapSSR = IndexMap<String, String, RandomState>;
+mod xpath;
 
-// Tokio global one-thread runtime
-static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
-    runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()
-        .unwrap()
-});
+type IndexMapSSR = IndexMap<String, String, RandomState>;
 
 #[pyclass(subclass)]
 /// HTTP client that can impersonate web browsers.
@@ -50,6 +104,8 @@ pub struct RClient {
     auth: Option<(String, Option<String>)>,
     #[pyo3(get, set)]
     auth_bearer: Option<String>,
+    cookies: Option<IndexMapSSR>,
+    cookie_jar: Option<Arc<CookieJar>>,
     #[pyo3(get, set)]
     params: Option<IndexMapSSR>,
     #[pyo3(get, set)]
@@ -60,6 +116,22 @@ pub struct RClient {
     impersonate: Option<String>,
     #[pyo3(get)]
     impersonate_os: Option<String>,
+    #[pyo3(get, set)]
+    raise_for_status: bool,
+    retry: Option<Retry>,
+    limiter: Option<Arc<HostLimiter>>,
+    // Maximum number of redirects to follow, None if redirects are not followed
+    max_redirects: Option<usize>,
+    referer: bool,
+    #[pyo3(get, set)]
+    decompress: bool,
+    #[pyo3(get)]
+    accept_encoding: Option<Vec<String>>,
+    #[pyo3(get, set)]
+    max_content_length: Option<u64>,
+    #[pyo3(get, set)]
+    max_decompressed_size: Option<u64>,
+    runtime: ClientRuntime,
 }
 
 #[pymethods]
@@ -90,6 +162,29 @@ impl RClient {
     /// * `ca_cert_file` - Path to CA certificate store. Default is None.
     /// * `https_only` - Restrict the Client to be used with HTTPS only requests. Default is `false`.
     /// * `http2_only` - If true - use only HTTP/2, if false - use only HTTP/1. Default is `false`.
+    /// * `runtime` - Tokio runtime to execute requests on: "current_thread" (global single-threaded runtime),
+    ///         "multi_thread" (global multi-threaded runtime) or "dedicated" (multi-threaded runtime owned
+    ///         by this client). Default is the env var PRIMP_RUNTIME or "current_thread".
+    /// * `worker_threads` - Number of worker threads for the "multi_thread" and "dedicated" runtimes.
+    ///         Default is the env var PRIMP_WORKER_THREADS or the number of CPUs.
+    /// * `raise_for_status` - Raise `HTTPStatusError` for responses with a 4xx or 5xx status code. Default is `false`.
+    /// * `retries` - The number of retries of failed requests, or a `Retry` policy. Default is None (no retries).
+    /// * `rate_limit` - Maximum number of requests per second per host. Requests over the limit wait
+    ///         for their turn. Default is None (no limit).
+    /// * `max_connections_per_host` - Maximum number of requests in flight per host. A streamed response
+    ///         keeps its slot until it is read or closed. Default is None (no limit).
+    /// * `decompress` - Decompress the response bodies according to their `Content-Encoding`. If false,
+    ///         the raw bodies are returned, with their `Content-Encoding` and `Content-Length` headers.
+    ///         Can be overridden per request. Default is `true`.
+    /// * `accept_encoding` - The encodings advertised in the `Accept-Encoding` header, among "gzip", "br",
+    ///         "zstd" and "deflate", overriding the impersonated header. An empty list sends "identity".
+    ///         Default is None: the impersonated header, or all of them.
+    /// * `max_content_length` - Maximum size of the response bodies as received, in bytes. Reading a
+    ///         larger body fails with `ResponseTooLarge`, before it is read if its `Content-Length` is
+    ///         larger. Can be overridden per request. Default is None (no limit).
+    /// * `max_decompressed_size` - Maximum size of the decompressed response bodies, in bytes.
+    ///         Decompressing a larger body fails with `ResponseTooLarge`, protecting against
+    ///         decompression bombs. Can be overridden per request. Default is None (no limit).
     ///
     /// # Example
     ///
@@ -113,12 +208,25 @@ impl RClient {
     ///     ca_cert_file="/cert/cacert.pem",
     ///     https_only=True,
     ///     http2_only=True,
+    ///     runtime="multi_thread",
+    ///     worker_threads=4,
+    ///     raise_for_status=True,
+    ///     retries=Retry(max_attempts=5, backoff_base=1.0),
+    ///     rate_limit=10,
+    ///     max_connections_per_host=4,
+    ///     decompress=True,
+    ///     accept_encoding=["gzip", "br"],
+    ///     max_content_length=10_000_000,
+    ///     max_decompressed_size=100_000_000,
     /// )
     /// ```
     #[new]
     #[pyo3(signature = (auth=None, auth_bearer=None, params=None, headers=None, cookies=None,
         cookie_store=true, referer=true, proxy=None, timeout=None, impersonate=None, impersonate_os=None, follow_redirects=true,
-        max_redirects=20, verify=true, ca_cert_file=None, https_only=false, http2_only=false))]
+        max_redirects=20, verify=true, ca_cert_file=None, https_only=false, http2_only=false,
+        runtime=None, worker_threads=None, raise_for_status=false, retries=None, rate_limit=None,
+        max_connections_per_host=None, decompress=true, accept_encoding=None,
+        max_content_length=None, max_decompressed_size=None))]
     fn new(
         auth: Option<(String, Option<String>)>,
         auth_bearer: Option<String>,
@@ -137,15 +245,25 @@ impl RClient {
         ca_cert_file: Option<String>,
         https_only: Option<bool>,
         http2_only: Option<bool>,
+        runtime: Option<String>,
+        worker_threads: Option<usize>,
+        raise_for_status: Option<bool>,
+        retries: Option<RetryArg>,
+        rate_limit: Option<f64>,
+        max_connections_per_host: Option<usize>,
+        decompress: bool,
+        accept_encoding: Option<Vec<String>>,
+        max_content_length: Option<u64>,
+        max_decompressed_size: Option<u64>,
     ) -> Result<Self> {
         // Client builder
         let mut client_builder = rquest::Client::builder();
 
         // Impersonate
         if let Some(impersonate) = &impersonate {
-            let imp = Impersonate::from_str(&impersonate.as_str())?;
+            let imp = Impersonate::from_str(impersonate.as_str())?;
             let imp_os = if let Some(impersonate_os) = &impersonate_os {
-                ImpersonateOS::from_str(&impersonate_os.as_str())?
+                ImpersonateOS::from_str(impersonate_os.as_str())?
             } else {
                 ImpersonateOS::default()
             };
@@ -156,24 +274,26 @@ impl RClient {
             client_builder = client_builder.impersonate(impersonate_builder);
         }
 
-        // Headers || Cookies
-        if headers.is_some() || cookies.is_some() {
-            let headers = headers.unwrap_or_else(|| IndexMap::with_hasher(RandomState::default()));
-            let mut headers_headermap = headers.to_headermap();
-            if let Some(cookies) = cookies {
-                let cookies_str = cookies.to_string();
-                headers_headermap.insert(COOKIE, HeaderValue::from_str(&cookies_str)?);
-            }
-            client_builder = client_builder.default_headers(headers_headermap);
+        // Headers. Cookies are added to each request, so that they are not sent to other hosts
+        // on redirects.
+        if let Some(headers) = headers {
+            client_builder = client_builder.default_headers(headers.to_headermap());
         };
+        if let Some(cookies) = &cookies {
+            HeaderValue::from_str(&cookies.to_string())?;
+        }
 
         // Cookie_store
-        if cookie_store.unwrap_or(true) {
-            client_builder = client_builder.cookie_store(true);
+        let cookie_jar = cookie_store
+            .unwrap_or(true)
+            .then(|| Arc::new(CookieJar::default()));
+        if let Some(cookie_jar) = &cookie_jar {
+            client_builder = client_builder.cookie_provider(cookie_jar.clone());
         }
 
         // Referer
-        if referer.unwrap_or(true) {
+        let referer = referer.unwrap_or(true);
+        if referer {
             client_builder = client_builder.referer(true);
         }
 
@@ -188,12 +308,11 @@ impl RClient {
             client_builder = client_builder.timeout(Duration::from_secs_f64(seconds));
         }
 
-        // Redirects
-        if follow_redirects.unwrap_or(true) {
-            client_builder = client_builder.redirect(Policy::limited(max_redirects.unwrap_or(20)));
-        } else {
-            client_builder = client_builder.redirect(Policy::none());
-        }
+        // Redirects. Requests follow them in `send_following_redirects`, to record the history.
+        let max_redirects = follow_redirects
+            .unwrap_or(true)
+            .then(|| max_redirects.unwrap_or(20));
+        client_builder = client_builder.redirect(Policy::none());
 
         // Ca_cert_file. BEFORE!!! verify (fn load_ca_certs() reads env var PRIMP_CA_BUNDLE)
         if let Some(ca_bundle_path) = &ca_cert_file {
@@ -217,26 +336,95 @@ impl RClient {
             client_builder = client_builder.http2_only();
         }
 
+        // Decompression is done by `BodyDecoder`, to be turned off per request. rquest is built
+        // without its decompression features, so it neither decompresses bodies nor adds
+        // `Accept-Encoding`: requests get it in `prepare_builder`
+        if let Some(encodings) = &accept_encoding {
+            accept_encoding_header(encodings)?;
+        }
+
+        // Timings of the DNS resolution and connection setup
+        client_builder = client_builder
+            .dns_resolver(Arc::new(TimingResolver::new(GaiResolver)))
+            .connector_layer(TimingLayer);
+
         let client = Arc::new(Mutex::new(client_builder.build()?));
 
+        // Runtime
+        let runtime = ClientRuntime::new(runtime, worker_threads)?;
+
+        // Per-host limits, shared by all the threads using the client
+        let limiter = HostLimiter::new(rate_limit, max_connections_per_host)?.map(Arc::new);
+
         Ok(RClient {
             client,
             auth,
             auth_bearer,
+            cookies,
+            cookie_jar,
             params,
             proxy,
             timeout,
             impersonate,
             impersonate_os,
+            raise_for_status: raise_for_status.unwrap_or(false),
+            retry: retries.map(Retry::from),
+            limiter,
+            max_redirects,
+            referer,
+            decompress,
+            accept_encoding,
+            max_content_length,
+            max_decompressed_size,
+            runtime,
         })
     }
 
+    /// The Tokio runtime mode requests are executed on: "current_thread", "multi_thread" or "dedicated".
+    #[getter]
+    pub fn get_runtime(&self) -> &'static str {
+        self.runtime.mode().as_str()
+    }
+
+    /// Number of runtime worker threads, or None for the "current_thread" runtime.
+    #[getter]
+    pub fn get_worker_threads(&self) -> Option<usize> {
+        self.runtime.worker_threads()
+    }
+
+    /// The retry policy of the client.
+    #[getter]
+    pub fn get_retries(&self) -> Option<Retry> {
+        self.retry.clone()
+    }
+
+    #[setter]
+    pub fn set_retries(&mut self, retries: Option<RetryArg>) {
+        self.retry = retries.map(Retry::from);
+    }
+
+    /// Maximum number of requests per second per host.
+    #[getter]
+    pub fn get_rate_limit(&self) -> Option<f64> {
+        self.limiter
+            .as_ref()
+            .and_then(|limiter| limiter.rate_limit())
+    }
+
+    /// Maximum number of requests in flight per host.
+    #[getter]
+    pub fn get_max_connections_per_host(&self) -> Option<usize> {
+        self.limiter
+            .as_ref()
+            .and_then(|limiter| limiter.max_concurrent())
+    }
+
     #[getter]
-    pub fn get_headers(&self) -> Result<IndexMapSSR> {
+    pub fn get_headers(&self) -> Headers {
         let client = self.client.lock().unwrap();
         let mut headers = client.headers().clone();
         headers.remove(COOKIE);
-        Ok(headers.to_indexmap())
+        Headers::from(headers)
     }
 
     #[setter]
@@ -254,31 +442,32 @@ impl RClient {
     }
 
     #[getter]
-    pub fn get_cookies(&self) -> Result<IndexMapSSR> {
-        let client = self.client.lock().unwrap();
-        let headers = client.headers();
-        let mut cookies: IndexMapSSR = IndexMap::with_hasher(RandomState::default());
-        if let Some(cookie_header) = headers.get(COOKIE) {
-            for part in cookie_header.to_str()?.split(';') {
-                if let Some((key, value)) = part.trim().split_once('=') {
-                    cookies.insert(key.to_string(), value.to_string());
-                }
-            }
-        }
-        Ok(cookies)
+    pub fn get_cookies(&self) -> IndexMapSSR {
+        self.cookies
+            .clone()
+            .unwrap_or_else(|| IndexMap::with_hasher(RandomState::default()))
     }
 
     #[setter]
-    pub fn set_cookies(&self, cookies: Option<IndexMapSSR>) -> Result<()> {
-        let mut client = self.client.lock().unwrap();
-        let mut mclient = client.as_mut();
-        let headers = mclient.headers();
-        if let Some(cookies) = cookies {
-            headers.insert(COOKIE, HeaderValue::from_str(&cookies.to_string())?);
+    pub fn set_cookies(&mut self, cookies: Option<IndexMapSSR>) -> Result<()> {
+        if let Some(cookies) = &cookies {
+            HeaderValue::from_str(&cookies.to_string())?;
+            self.cookies = Some(cookies.clone());
         }
         Ok(())
     }
 
+    /// The cookies of the cookie store sent to `url`, with their attributes.
+    ///
+    /// `cookies` only holds the cookies passed to the client. Empty without a cookie store.
+    pub fn get_cookie_objects(&self, url: &str) -> Result<Vec<Cookie>> {
+        let url = Url::parse(url)?;
+        Ok(self
+            .cookie_jar
+            .as_ref()
+            .map_or_else(Vec::new, |cookie_jar| cookie_jar.cookies_for(&url)))
+    }
+
     #[getter]
     pub fn get_proxy(&self) -> Result<Option<String>> {
         Ok(self.proxy.to_owned())
@@ -296,9 +485,9 @@ impl RClient {
     #[setter]
     pub fn set_impersonate(&mut self, impersonate: String) -> Result<()> {
         let mut client = self.client.lock().unwrap();
-        let imp = Impersonate::from_str(&impersonate.as_str())?;
+        let imp = Impersonate::from_str(impersonate.as_str())?;
         let imp_os = if let Some(impersonate_os) = &self.impersonate_os {
-            ImpersonateOS::from_str(&impersonate_os.as_str())?
+            ImpersonateOS::from_str(impersonate_os.as_str())?
         } else {
             ImpersonateOS::default()
         };
@@ -314,10 +503,10 @@ impl RClient {
     #[setter]
     pub fn set_impersonate_os(&mut self, impersonate_os: String) -> Result<()> {
         let mut client = self.client.lock().unwrap();
-        let imp_os = ImpersonateOS::from_str(&impersonate_os.as_str())?;
+        let imp_os = ImpersonateOS::from_str(impersonate_os.as_str())?;
         let mut impersonate_builder = Impersonate::builder().impersonate_os(imp_os);
         if let Some(impersonate) = &self.impersonate {
-            let imp = Impersonate::from_str(&impersonate.as_str())?;
+            let imp = Impersonate::from_str(impersonate.as_str())?;
             impersonate_builder = impersonate_builder.impersonate(imp);
         }
         client.as_mut().impersonate(impersonate_builder.build());
@@ -335,13 +524,23 @@ impl RClient {
     /// * `params` - A map of query parameters to append to the URL. Default is None.
     /// * `headers` - A map of HTTP headers to send with the request. Default is None.
     /// * `cookies` - An optional map of cookies to send with requests as the `Cookie` header.
-    /// * `content` - The content to send in the request body as bytes. Default is None.
+    /// * `content` - The content to send in the request body: bytes, or an iterator of bytes or a binary
+    ///     file-like object to stream with chunked transfer encoding. Default is None.
     /// * `data` - The form data to send in the request body. Default is None.
     /// * `json` -  A JSON serializable object to send in the request body. Default is None.
     /// * `files` - A map of file fields to file paths to be sent as multipart/form-data. Default is None.
     /// * `auth` - A tuple containing the username and an optional password for basic authentication. Default is None.
     /// * `auth_bearer` - A string representing the bearer token for bearer token authentication. Default is None.
     /// * `timeout` - The timeout for the request in seconds. Default is 30.
+    /// * `stream` - If true, the response body is not read upfront, but lazily with `iter_bytes`,
+    ///     `iter_text`, `iter_lines` or `read`. Default is false.
+    /// * `retries` - The number of retries, or a `Retry` policy, overriding the client one. Default is None.
+    /// * `decompress` - Decompress the response body, overriding the client option. If false, the raw body
+    ///     is returned, with its `Content-Encoding` and `Content-Length` headers. Default is None.
+    /// * `max_content_length` - Maximum size of the response body as received, in bytes, overriding
+    ///     the client option. Default is None.
+    /// * `max_decompressed_size` - Maximum size of the decompressed response body, in bytes, overriding
+    ///     the client option. Default is None.
     ///
     /// # Returns
     ///
@@ -351,36 +550,603 @@ impl RClient {
     ///
     /// * `PyException` - If there is an error making the request.
     #[pyo3(signature = (method, url, params=None, headers=None, cookies=None, content=None,
-        data=None, json=None, files=None, auth=None, auth_bearer=None, timeout=None))]
-    fn request(
+        data=None, json=None, files=None, auth=None, auth_bearer=None, timeout=None, stream=None,
+        retries=None, decompress=None, max_content_length=None, max_decompressed_size=None))]
+    fn request<'py>(
+        &self,
+        py: Python<'py>,
+        method: &str,
+        url: &str,
+        params: Option<IndexMapSSR>,
+        headers: Option<IndexMapSSR>,
+        cookies: Option<IndexMapSSR>,
+        content: Option<&Bound<'_, PyAny>>,
+        data: Option<&Bound<'_, PyAny>>,
+        json: Option<&Bound<'_, PyAny>>,
+        files: Option<IndexMap<String, String>>,
+        auth: Option<(String, Option<String>)>,
+        auth_bearer: Option<String>,
+        timeout: Option<f64>,
+        stream: Option<bool>,
+        retries: Option<RetryArg>,
+        decompress: Option<bool>,
+        max_content_length: Option<u64>,
+        max_decompressed_size: Option<u64>,
+    ) -> Result<Bound<'py, Response>> {
+        let future = self.prepare_request(
+            method,
+            url,
+            params,
+            headers,
+            cookies,
+            content,
+            data,
+            json,
+            files,
+            auth,
+            auth_bearer,
+            timeout,
+            stream,
+            retries,
+            decompress,
+            max_content_length,
+            max_decompressed_size,
+        )?;
+
+        // Execute an async future, releasing the Python GIL for concurrency.
+        // Use the client Tokio runtime to block on the future.
+        let result = py.allow_threads(|| self.runtime.block_on(future));
+        let proxied = self.proxy.is_some();
+        let response_data = result.map_err(|err| request_error(py, err, method, url, proxied))?;
+        Ok(response_data.into_py_response(py, self.raise_for_status)?)
+    }
+
+    /// Asynchronous version of `request`.
+    ///
+    /// Takes the same arguments as `request`, but returns a Python awaitable instead of blocking.
+    /// The request is driven by the Tokio runtime, so no Python thread is held while it is in flight.
+    /// The future is spawned on the client runtime, sharing its connections with `request`.
+    ///
+    /// # Returns
+    ///
+    /// * `Awaitable[Response]` - An awaitable resolving to the server's response.
+    ///
+    /// # Errors
+    ///
+    /// * `PyException` - If there is an error making the request.
+    #[pyo3(signature = (method, url, params=None, headers=None, cookies=None, content=None,
+        data=None, json=None, files=None, auth=None, auth_bearer=None, timeout=None, stream=None,
+        retries=None, decompress=None, max_content_length=None, max_decompressed_size=None))]
+    fn arequest<'py>(
+        &self,
+        py: Python<'py>,
+        method: &str,
+        url: &str,
+        params: Option<IndexMapSSR>,
+        headers: Option<IndexMapSSR>,
+        cookies: Option<IndexMapSSR>,
+        content: Option<&Bound<'_, PyAny>>,
+        data: Option<&Bound<'_, PyAny>>,
+        json: Option<&Bound<'_, PyAny>>,
+        files: Option<IndexMap<String, String>>,
+        auth: Option<(String, Option<String>)>,
+        auth_bearer: Option<String>,
+        timeout: Option<f64>,
+        stream: Option<bool>,
+        retries: Option<RetryArg>,
+        decompress: Option<bool>,
+        max_content_length: Option<u64>,
+        max_decompressed_size: Option<u64>,
+    ) -> Result<Bound<'py, PyAny>> {
+        let future = self.prepare_request(
+            method,
+            url,
+            params,
+            headers,
+            cookies,
+            content,
+            data,
+            json,
+            files,
+            auth,
+            auth_bearer,
+            timeout,
+            stream,
+            retries,
+            decompress,
+            max_content_length,
+            max_decompressed_size,
+        )?;
+
+        // The client runtime drives the future, on its own workers or its background thread
+        let handle = self.runtime.handle();
+        let (method, url) = (method.to_string(), url.to_string());
+        let raise_for_status = self.raise_for_status;
+        let proxied = self.proxy.is_some();
+        let awaitable = future_into_py(py, async move {
+            let response_data = run_on(handle, future).await?.map_err(|err| {
+                Python::with_gil(|py| request_error(py, err, &method, &url, proxied))
+            })?;
+            Python::with_gil(|py| {
+                let response = response_data.into_py_response(py, raise_for_status)?;
+                Ok(response.unbind())
+            })
+        })?;
+        Ok(awaitable)
+    }
+
+    /// Downloads the body of a GET request straight to a file, without buffering it in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to download.
+    /// * `path` - The path of the file to write the body to.
+    /// * `params` - A map of query parameters to append to the URL. Default is None.
+    /// * `headers` - A map of HTTP headers to send with the request. Default is None.
+    /// * `cookies` - An optional map of cookies to send with requests as the `Cookie` header.
+    /// * `auth` - A tuple containing the username and an optional password for basic authentication. Default is None.
+    /// * `auth_bearer` - A string representing the bearer token for bearer token authentication. Default is None.
+    /// * `timeout` - The timeout for the request in seconds. Default is 30.
+    /// * `resume` - If the file already exists, request only the rest of the body with a `Range`
+    ///     header and append it to the file. Default is true.
+    ///
+    /// The body is saved as received: `Accept-Encoding: identity` is sent, and a body the server
+    /// compresses anyway is not decompressed. So the `max_content_length` of the client applies
+    /// to the whole file, a resumed one included, while `decompress` and `max_decompressed_size`
+    /// don't.
+    ///
+    /// # Returns
+    ///
+    /// * `DownloadResult` - The number of bytes written, whether the download was resumed and the final URL.
+    ///
+    /// # Errors
+    ///
+    /// * `PyException` - If the request fails, or the written length does not match
+    ///     `Content-Length` / `Content-Range`.
+    /// * `HTTPStatusError` - If the server responds with a status other than 2xx. It carries the
+    ///     `status_code`, and no `response`, as the body is not read.
+    /// * `ResponseTooLarge` - If the body is larger than `max_content_length`.
+    #[pyo3(signature = (url, path, params=None, headers=None, cookies=None, auth=None,
+        auth_bearer=None, timeout=None, resume=true))]
+    fn download(
         &self,
         py: Python,
+        url: &str,
+        path: PathBuf,
+        params: Option<IndexMapSSR>,
+        headers: Option<IndexMapSSR>,
+        cookies: Option<IndexMapSSR>,
+        auth: Option<(String, Option<String>)>,
+        auth_bearer: Option<String>,
+        timeout: Option<f64>,
+        resume: bool,
+    ) -> Result<DownloadResult> {
+        let future = self.prepare_download(
+            url,
+            path,
+            params,
+            headers,
+            cookies,
+            auth,
+            auth_bearer,
+            timeout,
+            resume,
+        )?;
+        let result = py.allow_threads(|| self.runtime.block_on(future));
+        let proxied = self.proxy.is_some();
+        Ok(result.map_err(|err| request_error(py, err, "GET", url, proxied))?)
+    }
+
+    /// Asynchronous version of `download`.
+    #[pyo3(signature = (url, path, params=None, headers=None, cookies=None, auth=None,
+        auth_bearer=None, timeout=None, resume=true))]
+    fn adownload<'py>(
+        &self,
+        py: Python<'py>,
+        url: &str,
+        path: PathBuf,
+        params: Option<IndexMapSSR>,
+        headers: Option<IndexMapSSR>,
+        cookies: Option<IndexMapSSR>,
+        auth: Option<(String, Option<String>)>,
+        auth_bearer: Option<String>,
+        timeout: Option<f64>,
+        resume: bool,
+    ) -> Result<Bound<'py, PyAny>> {
+        let future = self.prepare_download(
+            url,
+            path,
+            params,
+            headers,
+            cookies,
+            auth,
+            auth_bearer,
+            timeout,
+            resume,
+        )?;
+        let handle = self.runtime.handle();
+        let url = url.to_string();
+        let proxied = self.proxy.is_some();
+        let awaitable = future_into_py(py, async move {
+            let result = run_on(handle, future).await?;
+            result
+                .map_err(|err| Python::with_gil(|py| request_error(py, err, "GET", &url, proxied)))
+        })?;
+        Ok(awaitable)
+    }
+
+    /// Sends a batch of requests concurrently on the client runtime, releasing the GIL once for the whole batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `requests` - A list of request specs: dicts with a `url` and the optional keyword arguments of `request`
+    ///     (`method` defaults to "GET").
+    /// * `concurrency` - The maximum number of requests in flight at once. Default is 10.
+    ///
+    /// # Returns
+    ///
+    /// * `list[Response | Exception]` - In the order of `requests`, the response, or the exception raised
+    ///     by the request that failed.
+    ///
+    /// # Errors
+    ///
+    /// * `PyException` - If `concurrency` is 0.
+    #[pyo3(signature = (requests, concurrency=None))]
+    fn request_many(
+        &self,
+        py: Python,
+        requests: Vec<Bound<'_, PyDict>>,
+        concurrency: Option<usize>,
+    ) -> Result<Vec<PyObject>> {
+        let future = self.prepare_batch(&requests, concurrency)?;
+        let targets = requests.iter().map(spec_target).collect();
+        let results = py.allow_threads(|| self.runtime.block_on(future));
+        let proxied = self.proxy.is_some();
+        batch_results_into_py(py, results, targets, self.raise_for_status, proxied)
+    }
+
+    /// Asynchronous version of `request_many`.
+    #[pyo3(signature = (requests, concurrency=None))]
+    fn arequest_many<'py>(
+        &self,
+        py: Python<'py>,
+        requests: Vec<Bound<'_, PyDict>>,
+        concurrency: Option<usize>,
+    ) -> Result<Bound<'py, PyAny>> {
+        let future = self.prepare_batch(&requests, concurrency)?;
+        let targets = requests.iter().map(spec_target).collect();
+        let handle = self.runtime.handle();
+        let raise_for_status = self.raise_for_status;
+        let proxied = self.proxy.is_some();
+        let awaitable = future_into_py(py, async move {
+            let results = run_on(handle, future).await?;
+            Ok(Python::with_gil(|py| {
+                batch_results_into_py(py, results, targets, raise_for_status, proxied)
+            })?)
+        })?;
+        Ok(awaitable)
+    }
+}
+
+impl RClient {
+    /// Builds the request future shared by `request` and `arequest`.
+    ///
+    /// Python arguments are converted while the GIL is held, so the returned future owns
+    /// all of its data and can be driven by any Tokio runtime.
+    fn prepare_request(
+        &self,
+        method: &str,
+        url: &str,
+        params: Option<IndexMapSSR>,
+        headers: Option<IndexMapSSR>,
+        cookies: Option<IndexMapSSR>,
+        content: Option<&Bound<'_, PyAny>>,
+        data: Option<&Bound<'_, PyAny>>,
+        json: Option<&Bound<'_, PyAny>>,
+        files: Option<IndexMap<String, String>>,
+        auth: Option<(String, Option<String>)>,
+        auth_bearer: Option<String>,
+        timeout: Option<f64>,
+        stream: Option<bool>,
+        retries: Option<RetryArg>,
+        decompress: Option<bool>,
+        max_content_length: Option<u64>,
+        max_decompressed_size: Option<u64>,
+    ) -> Result<impl Future<Output = Result<ResponseData>> + Send + 'static> {
+        let request_builder = self.prepare_builder(
+            method,
+            url,
+            params,
+            headers,
+            cookies,
+            content,
+            data,
+            json,
+            files,
+            auth,
+            auth_bearer,
+            timeout,
+        )?;
+        let stream = stream.unwrap_or(false);
+        let decompress = decompress.unwrap_or(self.decompress);
+        let limits = BodyLimits {
+            max_content_length: max_content_length.or(self.max_content_length),
+            max_decompressed_size: max_decompressed_size.or(self.max_decompressed_size),
+        };
+        let retry = retries
+            .map(Retry::from)
+            .or_else(|| self.retry.clone())
+            .filter(|retry| Method::from_bytes(method.as_bytes()).is_ok_and(|m| retry.allows(&m)));
+        let limiter = self.limiter.clone();
+        let max_redirects = self.max_redirects;
+        let referer = self.referer;
+        let method = method.to_string();
+        let request_url = url.to_string();
+        let runtime = self.runtime.clone();
+        let proxied = self.proxy.is_some();
+
+        Ok(async move {
+            // Send the request, following redirects, and await the response. Each request waits
+            // for a free slot of its host, the last one kept until the body is read.
+            let start = Instant::now();
+            let request_builder = request_builder.await?;
+            // Kept to reconnect event streams, see `Response.iter_events`
+            let replay = match stream {
+                true => Replay::new(
+                    &request_builder,
+                    max_redirects,
+                    referer,
+                    decompress,
+                    limits,
+                    limiter.clone(),
+                    &method,
+                    &request_url,
+                    &runtime,
+                    proxied,
+                ),
+                false => None,
+            };
+            let (result, connection_timings) = with_connection_timings(send_following_redirects(
+                request_builder,
+                max_redirects,
+                referer,
+                limiter,
+                |request_builder, limit| send_with_retry(request_builder, retry.clone(), limit),
+            ))
+            .await;
+            let Sent {
+                mut resp,
+                history,
+                permit,
+                waited,
+            } = result?;
+            // The time spent waiting for the host limits is not part of the timings
+            let start = start + waited;
+            let ttfb = start.elapsed();
+            let decoder = match decompress {
+                true => BodyDecoder::detect(resp.headers_mut(), limits.max_decompressed_size),
+                false => None,
+            };
+
+            // Response items
+            let cookies: IndexMapSSR = resp
+                .cookies()
+                .map(|cookie| (cookie.name().to_string(), cookie.value().to_string()))
+                .collect();
+            let headers = Headers::from(resp.headers().clone());
+            let status_code = resp.status().as_u16();
+            let url = resp.url().to_string();
+            let http_version = http_version_name(resp.version());
+            let remote_addr = resp.remote_addr().map(|addr| addr.to_string());
+            let mut body = BodyReader::new(resp, decoder, limits.max_content_length)?;
+            let (body, download) = if stream {
+                tracing::info!("response: {} {} (streamed)", url, status_code);
+                let stream = ResponseStream::new(body, method.clone(), runtime, permit, replay);
+                (ResponseBody::Stream(Box::new(stream)), None)
+            } else {
+                let buf = Bytes::from(body.read_to_end().await?);
+                tracing::info!("response: {} {} {}", url, status_code, buf.len());
+                (ResponseBody::Full(buf), Some(start.elapsed() - ttfb))
+            };
+            let timings = Timings::new(connection_timings, ttfb, download);
+
+            Ok(ResponseData {
+                body,
+                cookies,
+                headers,
+                status_code,
+                url,
+                method,
+                timings,
+                history,
+                http_version,
+                remote_addr,
+            })
+        })
+    }
+
+    /// Builds the request future of a `request_many` spec.
+    fn prepare_request_spec(
+        &self,
+        spec: &Bound<'_, PyDict>,
+    ) -> Result<impl Future<Output = Result<ResponseData>> + Send + 'static> {
+        for key in spec.keys() {
+            let key: String = key.extract()?;
+            if !REQUEST_SPEC_KEYS.contains(&key.as_str()) {
+                return Err(anyhow!("Unexpected key in request spec: {:?}", key));
+            }
+        }
+        let url: String = spec
+            .get_item("url")?
+            .ok_or_else(|| anyhow!("Request spec is missing the url"))?
+            .extract()?;
+        let method: Option<String> = spec_item(spec, "method")?;
+        let content = spec.get_item("content")?.filter(|v| !v.is_none());
+        let data = spec.get_item("data")?.filter(|v| !v.is_none());
+        let json = spec.get_item("json")?.filter(|v| !v.is_none());
+
+        self.prepare_request(
+            method.as_deref().unwrap_or("GET"),
+            &url,
+            spec_item(spec, "params")?,
+            spec_item(spec, "headers")?,
+            spec_item(spec, "cookies")?,
+            content.as_ref(),
+            data.as_ref(),
+            json.as_ref(),
+            spec_item(spec, "files")?,
+            spec_item(spec, "auth")?,
+            spec_item(spec, "auth_bearer")?,
+            spec_item(spec, "timeout")?,
+            spec_item(spec, "stream")?,
+            spec_item(spec, "retries")?,
+            spec_item(spec, "decompress")?,
+            spec_item(spec, "max_content_length")?,
+            spec_item(spec, "max_decompressed_size")?,
+        )
+    }
+
+    /// Builds the future of a `request_many` batch.
+    ///
+    /// Every request is spawned as its own task, at most `concurrency` of them in flight at once.
+    /// Specs that can't be converted fail in place, without failing the batch.
+    fn prepare_batch(
+        &self,
+        requests: &[Bound<'_, PyDict>],
+        concurrency: Option<usize>,
+    ) -> Result<impl Future<Output = Vec<Result<ResponseData>>> + Send + 'static> {
+        let concurrency = concurrency.unwrap_or(10);
+        if concurrency == 0 {
+            return Err(anyhow!("concurrency must be greater than 0"));
+        }
+        let futures: Vec<_> = requests
+            .iter()
+            .map(|spec| self.prepare_request_spec(spec))
+            .collect();
+        let semaphore = Arc::new(Semaphore::new(concurrency));
+
+        Ok(async move {
+            let handles: Vec<_> = futures
+                .into_iter()
+                .map(|future| {
+                    let semaphore = Arc::clone(&semaphore);
+                    tokio::spawn(async move {
+                        let future = future?;
+                        let _permit = semaphore.acquire_owned().await?;
+                        future.await
+                    })
+                })
+                .collect();
+            let mut results = Vec::with_capacity(handles.len());
+            for handle in handles {
+                results.push(handle.await.map_err(anyhow::Error::from).and_then(|r| r));
+            }
+            results
+        })
+    }
+
+    /// Builds the download future shared by `download` and `adownload`.
+    fn prepare_download(
+        &self,
+        url: &str,
+        path: PathBuf,
+        params: Option<IndexMapSSR>,
+        headers: Option<IndexMapSSR>,
+        cookies: Option<IndexMapSSR>,
+        auth: Option<(String, Option<String>)>,
+        auth_bearer: Option<String>,
+        timeout: Option<f64>,
+        resume: bool,
+    ) -> Result<impl Future<Output = Result<DownloadResult>> + Send + 'static> {
+        let request_builder = self.prepare_builder(
+            "GET",
+            url,
+            params,
+            headers,
+            cookies,
+            None,
+            None,
+            None,
+            None,
+            auth,
+            auth_bearer,
+            timeout,
+        )?;
+        let max_redirects = self.max_redirects;
+        let referer = self.referer;
+        let limiter = self.limiter.clone();
+        let max_content_length = self.max_content_length;
+        let retry = self
+            .retry
+            .clone()
+            .filter(|retry| retry.allows(&Method::GET));
+        Ok(async move {
+            let request_builder = request_builder.await?;
+            download_to_file(
+                request_builder,
+                path,
+                resume,
+                max_redirects,
+                referer,
+                limiter,
+                retry,
+                max_content_length,
+            )
+            .await
+        })
+    }
+
+    /// Builds the future creating the request builder, with the request options applied.
+    /// Async, because files of multipart bodies are opened with `tokio::fs`.
+    fn prepare_builder(
+        &self,
         method: &str,
         url: &str,
         params: Option<IndexMapSSR>,
         headers: Option<IndexMapSSR>,
         cookies: Option<IndexMapSSR>,
-        content: Option<Vec<u8>>,
+        content: Option<&Bound<'_, PyAny>>,
         data: Option<&Bound<'_, PyAny>>,
         json: Option<&Bound<'_, PyAny>>,
         files: Option<IndexMap<String, String>>,
         auth: Option<(String, Option<String>)>,
         auth_bearer: Option<String>,
         timeout: Option<f64>,
-    ) -> Result<Response> {
+    ) -> Result<impl Future<Output = Result<RequestBuilder>> + Send + 'static> {
         let client = Arc::clone(&self.client);
         let method = Method::from_bytes(method.as_bytes())?;
+        let url = url.to_string();
         let is_post_put_patch = matches!(method, Method::POST | Method::PUT | Method::PATCH);
         let params = params.or_else(|| self.params.clone());
+        let content_body: Option<Body> = content.map(content_to_body).transpose()?;
         let data_value: Option<Value> = data.map(depythonize).transpose()?;
         let json_value: Option<Value> = json.map(depythonize).transpose()?;
         let auth = auth.or(self.auth.clone());
         let auth_bearer = auth_bearer.or(self.auth_bearer.clone());
+        let cookies = cookies.or_else(|| self.cookies.clone());
         let timeout: Option<f64> = timeout.or(self.timeout);
+        let headers = headers.map(|headers| headers.to_headermap());
+
+        // Accept-Encoding, unless set by the request: the client option, else the client header
+        // (impersonated or default one), else all the encodings, as rquest would add it
+        let request_sets_it = headers
+            .as_ref()
+            .is_some_and(|headers| headers.contains_key(ACCEPT_ENCODING));
+        let client_sets_it = client
+            .lock()
+            .unwrap()
+            .headers()
+            .contains_key(ACCEPT_ENCODING);
+        let accept_encoding = match &self.accept_encoding {
+            _ if request_sets_it => None,
+            Some(encodings) => Some(accept_encoding_header(encodings)?),
+            None if client_sets_it => None,
+            None => Some(DEFAULT_ACCEPT_ENCODING),
+        };
 
-        let future = async {
+        Ok(async move {
             // Create request builder
-            let mut request_builder = client.lock().unwrap().request(method, url);
+            let mut request_builder = client.lock().unwrap().request(method, &url);
 
             // Params
             if let Some(params) = params {
@@ -389,7 +1155,10 @@ impl RClient {
 
             // Headers
             if let Some(headers) = headers {
-                request_builder = request_builder.headers(headers.to_headermap());
+                request_builder = request_builder.headers(headers);
+            }
+            if let Some(accept_encoding) = accept_encoding {
+                request_builder = request_builder.header(ACCEPT_ENCODING, accept_encoding);
             }
 
             // Cookies
@@ -401,7 +1170,7 @@ impl RClient {
             // Only if method POST || PUT || PATCH
             if is_post_put_patch {
                 // Content
-                if let Some(content) = content {
+                if let Some(content) = content_body {
                     request_builder = request_builder.body(content);
                 }
                 // Data
@@ -438,44 +1207,154 @@ impl RClient {
                 request_builder = request_builder.timeout(Duration::from_secs_f64(seconds));
             }
 
-            // Send the request and await the response
-            let resp = request_builder.send().await?;
+            Ok(request_builder)
+        })
+    }
+}
 
-            // Response items
-            let cookies: IndexMapSSR = resp
-                .cookies()
-                .map(|cookie| (cookie.name().to_string(), cookie.value().to_string()))
-                .collect();
-            let headers: IndexMapSSR = resp.headers().to_indexmap();
-            let status_code = resp.status().as_u16();
-            let url = resp.url().to_string();
-            let buf = resp.bytes().await?;
+/// Response body: read upfront, or left unread for `stream=True`.
+enum ResponseBody {
+    Full(Bytes),
+    Stream(Box<ResponseStream>),
+}
 
-            tracing::info!("response: {} {} {}", url, status_code, buf.len());
-            Ok((buf, cookies, headers, status_code, url))
-        };
+/// Response items collected inside the request future.
+///
+/// Converted into a `Response` once the GIL is held, because the body is stored as `PyBytes`.
+struct ResponseData {
+    body: ResponseBody,
+    cookies: IndexMapSSR,
+    headers: Headers,
+    status_code: u16,
+    url: String,
+    method: String,
+    timings: Timings,
+    history: Vec<RedirectResponse>,
+    http_version: &'static str,
+    remote_addr: Option<String>,
+}
 
-        // Execute an async future, releasing the Python GIL for concurrency.
-        // Use Tokio global runtime to block on the future.
-        let result: Result<(Bytes, IndexMapSSR, IndexMapSSR, u16, String), Error> =
-            py.allow_threads(|| RUNTIME.block_on(future));
-        let (f_buf, f_cookies, f_headers, f_status_code, f_url) = result?;
-
-        Ok(Response {
-            content: PyBytes::new(py, &f_buf).unbind(),
-            cookies: f_cookies,
+impl ResponseData {
+    fn into_response(self, py: Python) -> Response {
+        let (content, stream) = match self.body {
+            ResponseBody::Full(buf) => (Some(PyBytes::new(py, &buf).unbind()), None),
+            ResponseBody::Stream(stream) => (None, Some(*stream)),
+        };
+        Response {
+            content,
+            stream,
+            cookies: self.cookies,
             encoding: String::new(),
-            headers: f_headers,
-            status_code: f_status_code,
-            url: f_url,
-        })
+            headers: self.headers,
+            status_code: self.status_code,
+            url: self.url,
+            method: self.method,
+            timings: self.timings,
+            history: self.history,
+            http_version: self.http_version,
+            remote_addr: self.remote_addr,
+            document: None,
+        }
+    }
+
+    /// Converts into a Python `Response`, raising `HTTPStatusError` for 4xx and 5xx status codes
+    /// if `raise_for_status` is set.
+    fn into_py_response(
+        self,
+        py: Python<'_>,
+        raise_for_status: bool,
+    ) -> PyResult<Bound<'_, Response>> {
+        let response = Bound::new(py, self.into_response(py))?;
+        if raise_for_status {
+            Response::raise_for_status(&response)?;
+        }
+        Ok(response)
     }
 }
 
+// Keys accepted in the request specs of `request_many`
+const REQUEST_SPEC_KEYS: &[&str] = &[
+    "method",
+    "url",
+    "params",
+    "headers",
+    "cookies",
+    "content",
+    "data",
+    "json",
+    "files",
+    "auth",
+    "auth_bearer",
+    "timeout",
+    "stream",
+    "retries",
+    "decompress",
+    "max_content_length",
+    "max_decompressed_size",
+];
+
+/// Extracts an optional item of a request spec, treating `None` as missing.
+fn spec_item<'py, T: FromPyObject<'py>>(spec: &Bound<'py, PyDict>, key: &str) -> Result<Option<T>> {
+    Ok(spec
+        .get_item(key)?
+        .map(|value| value.extract::<Option<T>>())
+        .transpose()?
+        .flatten())
+}
+
+/// Method and URL of a request spec, to annotate the exception of a failed request.
+fn spec_target(spec: &Bound<'_, PyDict>) -> (String, String) {
+    let method = spec_item(spec, "method").ok().flatten();
+    let url = spec_item(spec, "url").ok().flatten();
+    (
+        method.unwrap_or_else(|| "GET".to_string()),
+        url.unwrap_or_default(),
+    )
+}
+
+/// Converts the results of a `request_many` batch into responses and exception objects.
+fn batch_results_into_py(
+    py: Python,
+    results: Vec<Result<ResponseData>>,
+    targets: Vec<(String, String)>,
+    raise_for_status: bool,
+    proxied: bool,
+) -> Result<Vec<PyObject>> {
+    results
+        .into_iter()
+        .zip(targets)
+        .map(|(result, (method, url))| match result {
+            Ok(response_data) => match response_data.into_py_response(py, raise_for_status) {
+                Ok(response) => Ok(response.into_any().unbind()),
+                Err(err) => Ok(err.into_value(py).into_any()),
+            },
+            Err(err) => Ok(request_error(py, err, &method, &url, proxied)
+                .into_value(py)
+                .into_any()),
+        })
+        .collect()
+}
+
 #[pymodule]
 fn primp(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     pyo3_log::init();
 
     m.add_class::<RClient>()?;
+    exceptions::register(m)?;
+    m.add_class::<DownloadResult>()?;
+    m.add_class::<Article>()?;
+    m.add_class::<Cookie>()?;
+    m.add_class::<Element>()?;
+    m.add_class::<Headers>()?;
+    m.add_class::<Link>()?;
+    m.add_class::<Metadata>()?;
+    m.add_class::<Retry>()?;
+    m.add_class::<RedirectResponse>()?;
+    m.add_class::<Timings>()?;
+    m.add_class::<BytesIterator>()?;
+    m.add_class::<TextIterator>()?;
+    m.add_class::<LinesIterator>()?;
+    m.add_class::<EventsIterator>()?;
+    m.add_class::<ServerSentEvent>()?;
     Ok(())
 }
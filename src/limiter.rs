@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use rquest::Url;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+// Number of hosts from which idle ones are evicted, doubled by the hosts still in use
+const MIN_PRUNE_AT: usize = 64;
+
+/// Per-host rate limit and concurrency cap, shared by all the threads using a client.
+///
+/// Requests over the limits wait inside the Tokio runtime instead of failing. The limits of
+/// idle hosts are evicted as new hosts are added, so that crawls don't grow the map forever.
+pub struct HostLimiter {
+    interval: Option<Duration>,
+    max_concurrent: Option<usize>,
+    hosts: Mutex<Hosts>,
+}
+
+struct Hosts {
+    limits: HashMap<String, Arc<HostLimit>>,
+    prune_at: usize,
+}
+
+impl HostLimiter {
+    /// Creates a limiter allowing `rate_limit` requests per second and `max_concurrent` requests
+    /// in flight per host. Returns None if neither limit is set.
+    pub fn new(rate_limit: Option<f64>, max_concurrent: Option<usize>) -> Result<Option<Self>> {
+        if rate_limit.is_some_and(|rate| !rate.is_finite() || rate <= 0.0) {
+            return Err(anyhow!("rate_limit must be a positive number"));
+        }
+        if max_concurrent == Some(0) {
+            return Err(anyhow!("max_connections_per_host must be greater than 0"));
+        }
+        if rate_limit.is_none() && max_concurrent.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(HostLimiter {
+            interval: rate_limit.map(|rate| Duration::from_secs_f64(1.0 / rate)),
+            max_concurrent,
+            hosts: Mutex::new(Hosts {
+                limits: HashMap::new(),
+                prune_at: MIN_PRUNE_AT,
+            }),
+        }))
+    }
+
+    pub fn rate_limit(&self) -> Option<f64> {
+        self.interval.map(|interval| 1.0 / interval.as_secs_f64())
+    }
+
+    pub fn max_concurrent(&self) -> Option<usize> {
+        self.max_concurrent
+    }
+
+    /// Limits of the host of `url`, or None if the URL has no host.
+    pub fn host(&self, url: &Url) -> Option<Arc<HostLimit>> {
+        let key = format!("{}:{}", url.host_str()?, url.port_or_known_default()?);
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(limit) = hosts.limits.get(&key) {
+            return Some(Arc::clone(limit));
+        }
+        if hosts.limits.len() >= hosts.prune_at {
+            let now = Instant::now();
+            hosts.limits.retain(|_, limit| !limit.is_idle(now));
+            hosts.prune_at = (hosts.limits.len() * 2).max(MIN_PRUNE_AT);
+        }
+        let limit = Arc::new(HostLimit {
+            interval: self.interval,
+            next_slot: Mutex::new(None),
+            semaphore: self.max_concurrent.map(|n| Arc::new(Semaphore::new(n))),
+        });
+        hosts.limits.insert(key, Arc::clone(&limit));
+        Some(limit)
+    }
+}
+
+/// Limits of a single host.
+pub struct HostLimit {
+    interval: Option<Duration>,
+    // Earliest time the next request may be sent
+    next_slot: Mutex<Option<Instant>>,
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl HostLimit {
+    /// Whether evicting the limits loses nothing: no request holds them or a slot, and the rate
+    /// window of the last request has passed.
+    fn is_idle(self: &Arc<Self>, now: Instant) -> bool {
+        Arc::strong_count(self) == 1
+            && self
+                .semaphore
+                .as_ref()
+                .is_none_or(|semaphore| Arc::strong_count(semaphore) == 1)
+            && self
+                .next_slot
+                .lock()
+                .unwrap()
+                .is_none_or(|next_slot| next_slot <= now)
+    }
+
+    /// Waits for a free concurrency slot. The slot is released when the permit is dropped.
+    pub async fn acquire(&self) -> Result<Option<OwnedSemaphorePermit>> {
+        match &self.semaphore {
+            Some(semaphore) => Ok(Some(Arc::clone(semaphore).acquire_owned().await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Waits until sending one more request keeps the host under its rate limit.
+    pub async fn wait_rate(&self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let now = Instant::now();
+            let slot = next_slot.map_or(now, |next| next.max(now));
+            *next_slot = Some(slot + interval);
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+#[cfg(test)]
+mod limiter_tests {
+    use super::*;
+
+    fn url(host: usize) -> Url {
+        Url::parse(&format!("https://host{}.example.com/", host)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_idle_hosts_evicted() {
+        let limiter = HostLimiter::new(Some(1e9), Some(1)).unwrap().unwrap();
+        let held = limiter.host(&url(0)).unwrap();
+        let permit = limiter.host(&url(1)).unwrap().acquire().await.unwrap();
+        for host in 2..1000 {
+            limiter.host(&url(host)).unwrap().wait_rate().await;
+        }
+        let hosts = limiter.hosts.lock().unwrap();
+        assert!(hosts.limits.len() <= 2 * MIN_PRUNE_AT);
+        // Limits in use are kept
+        assert!(Arc::ptr_eq(&hosts.limits["host0.example.com:443"], &held));
+        assert!(hosts.limits.contains_key("host1.example.com:443"));
+        drop(permit);
+    }
+}
@@ -0,0 +1,246 @@
+use foldhash::fast::RandomState;
+use foldhash::HashSet;
+use indexmap::IndexMap;
+use pyo3::prelude::*;
+use rquest::Url;
+
+use crate::html::Document;
+
+/// `rel` values of the `<link>` elements loading a resource of the page.
+const ASSET_RELS: &[&str] = &[
+    "apple-touch-icon",
+    "apple-touch-icon-precomposed",
+    "icon",
+    "manifest",
+    "mask-icon",
+    "modulepreload",
+    "preload",
+    "prefetch",
+    "stylesheet",
+];
+
+/// A URL referenced by an HTML document, resolved to an absolute URL.
+#[pyclass(frozen)]
+#[derive(Clone, Debug)]
+pub struct Link {
+    #[pyo3(get)]
+    pub url: String,
+    /// The text of an anchor, or the `alt` text of an image.
+    #[pyo3(get)]
+    pub text: String,
+    /// The tag name of the element referencing the URL: "a", "img", "script"...
+    #[pyo3(get)]
+    pub tag: String,
+    /// The lowercase `rel` values, e.g. ["nofollow"] or ["stylesheet"].
+    #[pyo3(get)]
+    pub rel: Vec<String>,
+    #[pyo3(get)]
+    pub attrs: IndexMap<String, String, RandomState>,
+}
+
+#[pymethods]
+impl Link {
+    fn __repr__(&self) -> String {
+        format!("Link(<{}> {:?})", self.tag, self.url)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LinkKind {
+    /// `<a>` and `<area>` hyperlinks.
+    Links,
+    /// `<img>` sources and `srcset` candidates, and `<picture>` sources.
+    Images,
+    /// The resources loaded by the page: images, scripts, stylesheets, icons, media, frames...
+    Assets,
+}
+
+/// The URLs of the kind `kind` referenced by `doc`, in document order.
+///
+/// Relative URLs are resolved against the first `<base href>`, itself resolved against `url`.
+/// Only http and https URLs are kept. With `same_host`, only the URLs on the host of `url` are
+/// kept, and with `unique`, only the first occurrence of each URL.
+pub fn extract(
+    doc: &Document,
+    url: &str,
+    kind: LinkKind,
+    same_host: bool,
+    unique: bool,
+) -> Vec<Link> {
+    let Ok(url) = Url::parse(url) else {
+        return Vec::new();
+    };
+    let base = base_url(doc, &url);
+
+    let mut seen = HashSet::default();
+    let mut links = Vec::new();
+    for id in doc.descendants(Document::ROOT) {
+        let Some(tag) = doc.name(id) else {
+            continue;
+        };
+        for reference in references(doc, id, tag, kind) {
+            let Ok(resolved) = base.join(reference.trim()) else {
+                continue;
+            };
+            if !matches!(resolved.scheme(), "http" | "https")
+                || (same_host && resolved.host_str() != url.host_str())
+                || (unique && !seen.insert(resolved.to_string()))
+            {
+                continue;
+            }
+            let text = match tag {
+                "a" => normalize_space(&doc.text(id)),
+                _ => doc.attr(id, "alt").map(normalize_space).unwrap_or_default(),
+            };
+            links.push(Link {
+                url: resolved.into(),
+                text,
+                tag: tag.to_string(),
+                rel: rel(doc, id),
+                attrs: doc.attrs(id).iter().cloned().collect(),
+            });
+        }
+    }
+    links
+}
+
+/// The URL relative URLs are resolved against: the first `<base href>`, resolved against `url`,
+/// or `url`.
+pub fn base_url(doc: &Document, url: &Url) -> Url {
+    doc.descendants(Document::ROOT)
+        .find(|&id| doc.name(id) == Some("base") && doc.attr(id, "href").is_some())
+        .and_then(|id| url.join(doc.attr(id, "href")?.trim()).ok())
+        .unwrap_or_else(|| url.clone())
+}
+
+/// The raw URLs of the kind `kind` referenced by the element `id`.
+fn references<'a>(doc: &'a Document, id: usize, tag: &str, kind: LinkKind) -> Vec<&'a str> {
+    let parent = doc.node(id).parent.and_then(|parent| doc.name(parent));
+    let mut references = Vec::new();
+    let mut push_attr = |name: &str| references.extend(doc.attr(id, name));
+    match (kind, tag) {
+        (LinkKind::Links, "a" | "area") => push_attr("href"),
+        (LinkKind::Links, _) => {}
+        (_, "img") => {
+            push_attr("src");
+            references.extend(doc.attr(id, "srcset").into_iter().flat_map(srcset));
+        }
+        (_, "source") if parent == Some("picture") => {
+            references.extend(doc.attr(id, "srcset").into_iter().flat_map(srcset))
+        }
+        (LinkKind::Images, _) => {}
+        (_, "link")
+            if rel(doc, id)
+                .iter()
+                .any(|rel| ASSET_RELS.contains(&rel.as_str())) =>
+        {
+            push_attr("href")
+        }
+        (_, "video") => {
+            push_attr("src");
+            push_attr("poster");
+        }
+        (_, "script" | "audio" | "source" | "track" | "iframe" | "embed") => push_attr("src"),
+        (_, "object") => push_attr("data"),
+        _ => {}
+    }
+    references
+}
+
+/// The URLs of the candidates of a `srcset` attribute.
+fn srcset(srcset: &str) -> impl Iterator<Item = &str> {
+    srcset
+        .split(',')
+        .filter_map(|candidate| candidate.split_ascii_whitespace().next())
+}
+
+/// The lowercase values of the `rel` attribute of the element `id`.
+pub fn rel(doc: &Document, id: usize) -> Vec<String> {
+    doc.attr(id, "rel")
+        .map(|rel| {
+            rel.split_ascii_whitespace()
+                .map(|value| value.to_ascii_lowercase())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Collapses the runs of whitespace into single spaces, and trims the ends.
+pub fn normalize_space(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod links_tests {
+    use super::*;
+
+    const HTML: &str = r#"<html><head>
+<base href="/docs/">
+<link rel="stylesheet" href="style.css"><link rel="canonical" href="https://example.com/docs/">
+<link rel="Shortcut Icon" href="/favicon.ico"><script src="app.js"></script>
+</head><body>
+<a href="page.html" rel="nofollow">The  <b>page</b></a>
+<a href="https://other.org/">Other</a> <a href="mailto:me@example.com">Mail</a>
+<a href="page.html#top">Top</a> <a href="page.html">Again</a> <a>No href</a>
+<img src="a.png" alt="A" srcset="a-2x.png 2x, a-3x.png 3x">
+<picture><source srcset="b.webp"><img src="b.png"></picture>
+<video src="v.mp4" poster="v.jpg"><track src="v.vtt"></video>
+</body></html>"#;
+
+    fn urls(kind: LinkKind, same_host: bool, unique: bool) -> Vec<String> {
+        let doc = Document::parse(HTML);
+        extract(
+            &doc,
+            "https://example.com/index.html",
+            kind,
+            same_host,
+            unique,
+        )
+        .into_iter()
+        .map(|link| link.url)
+        .collect()
+    }
+
+    #[test]
+    fn test_links() {
+        let doc = Document::parse(HTML);
+        let links = extract(&doc, "https://example.com/", LinkKind::Links, false, true);
+        assert_eq!(links[0].url, "https://example.com/docs/page.html");
+        assert_eq!(links[0].text, "The page");
+        assert_eq!(links[0].rel, vec!["nofollow"]);
+        assert_eq!(links[0].attrs.get("href").unwrap(), "page.html");
+        assert_eq!(
+            urls(LinkKind::Links, false, true),
+            vec![
+                "https://example.com/docs/page.html",
+                "https://other.org/",
+                "https://example.com/docs/page.html#top",
+            ]
+        );
+        assert_eq!(urls(LinkKind::Links, true, false).len(), 3);
+    }
+
+    #[test]
+    fn test_images_and_assets() {
+        assert_eq!(
+            urls(LinkKind::Images, false, true),
+            vec![
+                "https://example.com/docs/a.png",
+                "https://example.com/docs/a-2x.png",
+                "https://example.com/docs/a-3x.png",
+                "https://example.com/docs/b.webp",
+                "https://example.com/docs/b.png",
+            ]
+        );
+        let assets = urls(LinkKind::Assets, false, true);
+        assert_eq!(
+            assets[..3],
+            [
+                "https://example.com/docs/style.css",
+                "https://example.com/favicon.ico",
+                "https://example.com/docs/app.js",
+            ]
+        );
+        assert_eq!(assets.len(), 11);
+    }
+}
@@ -0,0 +1,294 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use html2text::config::{self, Config};
+use html2text::render::{PlainDecorator, TaggedLine, TextDecorator, TrivialDecorator};
+
+/// How tables are rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tables {
+    /// Cells in columns, separated by borders (default).
+    Borders,
+    /// Cells in columns, without borders.
+    NoBorders,
+    /// Each cell on its own lines, for text extraction.
+    Raw,
+}
+
+impl FromStr for Tables {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "borders" => Ok(Tables::Borders),
+            "no_borders" => Ok(Tables::NoBorders),
+            "raw" => Ok(Tables::Raw),
+            _ => Err(anyhow!("Invalid tables: {:?}", s)),
+        }
+    }
+}
+
+/// Options of the markdown conversion. The defaults are the ones of `text_markdown`.
+#[derive(Clone, Copy, Debug)]
+pub struct MarkdownOptions {
+    /// The width lines are wrapped at.
+    pub width: usize,
+    /// Links as `[text][1]` with a list of `[1]: url` footnotes, instead of `[text](url)`.
+    pub link_footnotes: bool,
+    /// Images as `[alt]`, instead of nothing. Images without alt text are always dropped.
+    pub image_alt: bool,
+    pub tables: Tables,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        MarkdownOptions {
+            width: 100,
+            link_footnotes: true,
+            image_alt: true,
+            tables: Tables::Borders,
+        }
+    }
+}
+
+/// Converts `html` to markdown.
+pub fn to_markdown(html: &str, options: &MarkdownOptions) -> Result<String> {
+    let decorator = MarkdownDecorator {
+        plain: PlainDecorator::new(),
+        link_footnotes: options.link_footnotes,
+        image_alt: options.image_alt,
+        link_urls: Rc::default(),
+    };
+    render(
+        config::with_decorator(decorator),
+        html,
+        options.width,
+        options.tables,
+    )
+}
+
+/// Options of the plain and rich text conversions. The defaults are the ones of `text_plain`.
+#[derive(Clone, Copy, Debug)]
+pub struct TextOptions {
+    /// The width lines are wrapped at.
+    pub width: usize,
+    /// Emphasis, links and images marked up like `*this*`, as in `text_rich`, instead of
+    /// plain text.
+    pub rich: bool,
+    pub tables: Tables,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            width: 100,
+            rich: false,
+            tables: Tables::Borders,
+        }
+    }
+}
+
+/// Converts `html` to plain or rich text.
+pub fn to_text(html: &str, options: &TextOptions) -> Result<String> {
+    match options.rich {
+        true => render(config::rich(), html, options.width, options.tables),
+        false => render(
+            config::with_decorator(TrivialDecorator::new()),
+            html,
+            options.width,
+            options.tables,
+        ),
+    }
+}
+
+fn render<D: TextDecorator>(
+    config: Config<D>,
+    html: &str,
+    width: usize,
+    tables: Tables,
+) -> Result<String> {
+    let config = match tables {
+        Tables::Borders => config,
+        Tables::NoBorders => config.no_table_borders(),
+        Tables::Raw => config.raw_mode(true),
+    };
+    Ok(config.string_from_read(html.as_bytes(), width)?)
+}
+
+/// The `PlainDecorator` of `text_markdown`, with inline links and without images as options.
+#[derive(Clone)]
+struct MarkdownDecorator {
+    plain: PlainDecorator,
+    link_footnotes: bool,
+    image_alt: bool,
+    // URLs of the links being rendered inline, shared with the decorators of table cells
+    link_urls: Rc<RefCell<Vec<String>>>,
+}
+
+impl TextDecorator for MarkdownDecorator {
+    type Annotation = ();
+
+    fn decorate_link_start(&mut self, url: &str) -> (String, ()) {
+        if self.link_footnotes {
+            return self.plain.decorate_link_start(url);
+        }
+        self.link_urls.borrow_mut().push(url.to_string());
+        ("[".to_string(), ())
+    }
+
+    fn decorate_link_end(&mut self) -> String {
+        if self.link_footnotes {
+            return self.plain.decorate_link_end();
+        }
+        let url = self.link_urls.borrow_mut().pop().unwrap_or_default();
+        format!("]({})", url)
+    }
+
+    fn decorate_em_start(&self) -> (String, ()) {
+        self.plain.decorate_em_start()
+    }
+
+    fn decorate_em_end(&self) -> String {
+        self.plain.decorate_em_end()
+    }
+
+    fn decorate_strong_start(&self) -> (String, ()) {
+        self.plain.decorate_strong_start()
+    }
+
+    fn decorate_strong_end(&self) -> String {
+        self.plain.decorate_strong_end()
+    }
+
+    fn decorate_strikeout_start(&self) -> (String, ()) {
+        self.plain.decorate_strikeout_start()
+    }
+
+    fn decorate_strikeout_end(&self) -> String {
+        self.plain.decorate_strikeout_end()
+    }
+
+    fn decorate_code_start(&self) -> (String, ()) {
+        self.plain.decorate_code_start()
+    }
+
+    fn decorate_code_end(&self) -> String {
+        self.plain.decorate_code_end()
+    }
+
+    fn decorate_preformat_first(&self) {}
+
+    fn decorate_preformat_cont(&self) {}
+
+    fn decorate_image(&mut self, src: &str, title: &str) -> (String, ()) {
+        match self.image_alt {
+            true => self.plain.decorate_image(src, title),
+            false => (String::new(), ()),
+        }
+    }
+
+    fn header_prefix(&self, level: usize) -> String {
+        self.plain.header_prefix(level)
+    }
+
+    fn quote_prefix(&self) -> String {
+        self.plain.quote_prefix()
+    }
+
+    fn unordered_item_prefix(&self) -> String {
+        self.plain.unordered_item_prefix()
+    }
+
+    fn ordered_item_prefix(&self, i: i64) -> String {
+        self.plain.ordered_item_prefix(i)
+    }
+
+    fn make_subblock_decorator(&self) -> Self {
+        self.clone()
+    }
+
+    fn finalise(&mut self, links: Vec<String>) -> Vec<TaggedLine<()>> {
+        match self.link_footnotes {
+            true => self.plain.finalise(links),
+            false => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod markdown_tests {
+    use super::*;
+    use html2text::render::RichDecorator;
+
+    const HTML: &str = r#"<h1>Title</h1>
+<p>A <a href="https://example.com/">link</a> and <img src="a.png" alt="an image">.</p>
+<table><tr><td>a</td><td>b</td></tr></table>"#;
+
+    #[test]
+    fn test_default_options() {
+        let markdown = to_markdown(HTML, &MarkdownOptions::default()).unwrap();
+        assert_eq!(
+            markdown,
+            html2text::from_read(HTML.as_bytes(), 100).unwrap()
+        );
+        assert!(markdown.contains("A [link][1] and [an image]."));
+        assert!(markdown.contains("[1]: https://example.com/"));
+    }
+
+    #[test]
+    fn test_options() {
+        let options = MarkdownOptions {
+            link_footnotes: false,
+            image_alt: false,
+            tables: Tables::NoBorders,
+            ..Default::default()
+        };
+        let markdown = to_markdown(HTML, &options).unwrap();
+        assert!(markdown.contains("A [link](https://example.com/) and ."));
+        assert!(!markdown.contains("[1]") && !markdown.contains('│'));
+
+        let options = MarkdownOptions {
+            width: 20,
+            tables: Tables::Raw,
+            ..Default::default()
+        };
+        let markdown = to_markdown(HTML, &options).unwrap();
+        assert!(markdown.lines().all(|line| line.chars().count() <= 20));
+        assert!(markdown.contains("a\nb\n") || markdown.contains("a\n\nb\n"));
+        assert!("grid".parse::<Tables>().is_err());
+    }
+
+    #[test]
+    fn test_text_options() {
+        let text = to_text(HTML, &TextOptions::default()).unwrap();
+        assert_eq!(
+            text,
+            html2text::from_read_with_decorator(HTML.as_bytes(), 100, TrivialDecorator::new())
+                .unwrap()
+        );
+        assert!(text.contains("A link and an image."));
+        assert!(text.contains('│'));
+
+        let options = TextOptions {
+            rich: true,
+            ..Default::default()
+        };
+        let text = to_text(HTML, &options).unwrap();
+        assert_eq!(
+            text,
+            html2text::from_read_with_decorator(HTML.as_bytes(), 100, RichDecorator::new())
+                .unwrap()
+        );
+
+        let options = TextOptions {
+            width: 20,
+            tables: Tables::NoBorders,
+            ..Default::default()
+        };
+        let text = to_text(HTML, &options).unwrap();
+        assert!(text.lines().all(|line| line.chars().count() <= 20));
+        assert!(!text.contains('│'));
+    }
+}
@@ -0,0 +1,203 @@
+use foldhash::fast::RandomState;
+use indexmap::IndexMap;
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+use rquest::Url;
+
+use crate::html::Document;
+use crate::json::{loads, JsonOptions};
+use crate::links::{base_url, normalize_space, rel};
+
+/// JSON-LD is parsed leniently: control characters in strings are common in the wild.
+const JSON_LD_OPTIONS: JsonOptions = JsonOptions {
+    big_int: false,
+    decimal: false,
+    strict: false,
+};
+
+/// Metadata of an HTML document, from its `<head>`.
+///
+/// Missing fields are None, or empty. URLs are resolved to absolute URLs.
+#[pyclass(frozen)]
+#[derive(Debug, Default)]
+pub struct Metadata {
+    /// The text of `<title>`.
+    #[pyo3(get)]
+    pub title: Option<String>,
+    /// The `description` meta tag.
+    #[pyo3(get)]
+    pub description: Option<String>,
+    /// The `lang` attribute of `<html>`, or the `Content-Language` meta tag.
+    #[pyo3(get)]
+    pub language: Option<String>,
+    /// The `<link rel="canonical">` URL.
+    #[pyo3(get)]
+    pub canonical: Option<String>,
+    /// The first `<link rel="icon">` URL.
+    #[pyo3(get)]
+    pub favicon: Option<String>,
+    /// The `og:` meta tags, without the prefix: {"title": ..., "image": ...}. The first one wins.
+    #[pyo3(get)]
+    pub opengraph: IndexMap<String, String, RandomState>,
+    /// The `twitter:` meta tags, without the prefix: {"card": ..., "site": ...}.
+    #[pyo3(get)]
+    pub twitter: IndexMap<String, String, RandomState>,
+    // Contents of the `<script type="application/ld+json">` elements, parsed on access
+    json_ld: Vec<String>,
+}
+
+impl Metadata {
+    /// Extracts the metadata of `doc`, resolving URLs against its `<base href>` and `url`.
+    pub fn extract(doc: &Document, url: &str) -> Self {
+        let mut metadata = Metadata {
+            json_ld: json_ld_blocks(doc).collect(),
+            ..Default::default()
+        };
+        let base = Url::parse(url).ok().map(|url| base_url(doc, &url));
+        let resolve = |href: &str| match &base {
+            Some(base) => base.join(href.trim()).ok().map(String::from),
+            None => Some(href.trim().to_string()),
+        };
+        for id in doc.descendants(Document::ROOT) {
+            match doc.name(id) {
+                Some("html") if metadata.language.is_none() => {
+                    metadata.language = non_empty(doc.attr(id, "lang"));
+                }
+                Some("title") if metadata.title.is_none() => {
+                    metadata.title = non_empty(Some(&doc.text(id)));
+                }
+                Some("meta") => {
+                    let Some(content) = doc.attr(id, "content") else {
+                        continue;
+                    };
+                    if let Some(http_equiv) = doc.attr(id, "http-equiv") {
+                        if http_equiv.eq_ignore_ascii_case("content-language")
+                            && metadata.language.is_none()
+                        {
+                            metadata.language = non_empty(Some(content));
+                        }
+                        continue;
+                    }
+                    // OpenGraph uses `property`, but `name` is common too
+                    let Some(key) = doc.attr(id, "property").or(doc.attr(id, "name")) else {
+                        continue;
+                    };
+                    let key = key.trim().to_ascii_lowercase();
+                    let content = content.trim().to_string();
+                    if let Some(key) = key.strip_prefix("og:") {
+                        metadata.opengraph.entry(key.to_string()).or_insert(content);
+                    } else if let Some(key) = key.strip_prefix("twitter:") {
+                        metadata.twitter.entry(key.to_string()).or_insert(content);
+                    } else if key == "description" && metadata.description.is_none() {
+                        metadata.description = non_empty(Some(&content));
+                    }
+                }
+                Some("link") => {
+                    let Some(href) = doc.attr(id, "href") else {
+                        continue;
+                    };
+                    let rel = rel(doc, id);
+                    if rel.iter().any(|rel| rel == "canonical") && metadata.canonical.is_none() {
+                        metadata.canonical = resolve(href);
+                    }
+                    if rel.iter().any(|rel| rel == "icon") && metadata.favicon.is_none() {
+                        metadata.favicon = resolve(href);
+                    }
+                }
+                _ => {}
+            }
+        }
+        metadata
+    }
+}
+
+#[pymethods]
+impl Metadata {
+    /// The objects of the valid `<script type="application/ld+json">` blocks. Top-level arrays
+    /// are flattened.
+    #[getter]
+    fn json_ld(&self, py: Python) -> Vec<PyObject> {
+        let mut objects = Vec::new();
+        for block in &self.json_ld {
+            let Ok(value) = loads(py, block.trim().as_bytes(), JSON_LD_OPTIONS, false) else {
+                continue;
+            };
+            match value.downcast_bound::<PyList>(py) {
+                Ok(list) => objects.extend(list.iter().map(Bound::unbind)),
+                Err(_) => objects.push(value),
+            }
+        }
+        objects
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Metadata(title={:?}, language={:?}, canonical={:?})",
+            self.title, self.language, self.canonical
+        )
+    }
+}
+
+/// The contents of the `<script type="application/ld+json">` elements.
+fn json_ld_blocks(doc: &Document) -> impl Iterator<Item = String> + '_ {
+    doc.descendants(Document::ROOT)
+        .filter(move |&id| {
+            doc.name(id) == Some("script")
+                && doc
+                    .attr(id, "type")
+                    .is_some_and(|t| t.trim().eq_ignore_ascii_case("application/ld+json"))
+        })
+        .map(|id| doc.text(id))
+}
+
+fn non_empty(text: Option<&str>) -> Option<String> {
+    text.map(normalize_space).filter(|text| !text.is_empty())
+}
+
+#[cfg(test)]
+mod metadata_tests {
+    use super::*;
+
+    const HTML: &str = r#"<!DOCTYPE html>
+<html lang="en-US"><head>
+<title>
+  The   Title
+</title>
+<meta name="Description" content=" A page. ">
+<meta property="og:title" content="OG Title"><meta property="og:image" content="https://cdn.example.com/1.png">
+<meta property="og:image" content="https://cdn.example.com/2.png">
+<meta name="twitter:card" content="summary">
+<link rel="canonical" href="/page"><link rel="shortcut icon" href="/favicon.ico">
+<script type="application/ld+json">{"@type": "Article"}</script>
+</head><body><svg><title>Icon</title></svg></body></html>"#;
+
+    #[test]
+    fn test_metadata() {
+        let doc = Document::parse(HTML);
+        let metadata = Metadata::extract(&doc, "https://example.com/page?utm=1");
+        assert_eq!(metadata.title.as_deref(), Some("The Title"));
+        assert_eq!(metadata.description.as_deref(), Some("A page."));
+        assert_eq!(metadata.language.as_deref(), Some("en-US"));
+        assert_eq!(
+            metadata.canonical.as_deref(),
+            Some("https://example.com/page")
+        );
+        assert_eq!(
+            metadata.favicon.as_deref(),
+            Some("https://example.com/favicon.ico")
+        );
+        assert_eq!(metadata.opengraph["title"], "OG Title");
+        assert_eq!(metadata.opengraph["image"], "https://cdn.example.com/1.png");
+        assert_eq!(metadata.twitter["card"], "summary");
+        assert_eq!(metadata.json_ld, vec![r#"{"@type": "Article"}"#]);
+    }
+
+    #[test]
+    fn test_missing_metadata() {
+        let doc = Document::parse("<p>No head</p>");
+        let metadata = Metadata::extract(&doc, "https://example.com/");
+        assert!(metadata.title.is_none() && metadata.description.is_none());
+        assert!(metadata.language.is_none() && metadata.canonical.is_none());
+        assert!(metadata.opengraph.is_empty() && metadata.twitter.is_empty());
+    }
+}
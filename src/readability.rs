@@ -0,0 +1,627 @@
+use anyhow::Result;
+use pyo3::prelude::*;
+
+use crate::html::{Document, NodeData};
+use crate::links::normalize_space;
+use crate::markdown::{to_markdown, to_text, MarkdownOptions, TextOptions};
+use crate::metadata::Metadata;
+
+// Elements that never hold the main content
+const SKIPPED_TAGS: &[&str] = &[
+    "aside", "button", "canvas", "dialog", "embed", "footer", "iframe", "input", "nav", "noscript",
+    "object", "script", "select", "style", "svg", "template", "textarea",
+];
+
+// The `role`s of the elements that never hold the main content
+const SKIPPED_ROLES: &[&str] = &[
+    "alert",
+    "alertdialog",
+    "banner",
+    "complementary",
+    "contentinfo",
+    "dialog",
+    "menu",
+    "menubar",
+    "navigation",
+];
+
+// Class and id substrings of boilerplate, unless they match MAYBE_CANDIDATE
+const UNLIKELY_CANDIDATE: &[&str] = &[
+    "-ad-",
+    "ai2html",
+    "banner",
+    "breadcrumbs",
+    "combx",
+    "comment",
+    "community",
+    "consent",
+    "cookie",
+    "cover-wrap",
+    "disqus",
+    "extra",
+    "footer",
+    "gdpr",
+    "header",
+    "legends",
+    "menu",
+    "modal",
+    "newsletter",
+    "popup",
+    "related",
+    "remark",
+    "replies",
+    "rss",
+    "share",
+    "shoutbox",
+    "sidebar",
+    "skyscraper",
+    "social",
+    "sponsor",
+    "subscribe",
+    "supplemental",
+    "ad-break",
+    "agegate",
+    "pagination",
+    "pager",
+];
+
+const MAYBE_CANDIDATE: &[&str] = &[
+    "and", "article", "body", "column", "content", "main", "shadow",
+];
+
+const POSITIVE: &[&str] = &[
+    "article", "body", "content", "entry", "hentry", "h-entry", "main", "page", "post", "text",
+    "blog", "story",
+];
+
+const NEGATIVE: &[&str] = &[
+    "-ad-",
+    "hidden",
+    "banner",
+    "combx",
+    "comment",
+    "com-",
+    "contact",
+    "foot",
+    "gdpr",
+    "masthead",
+    "media",
+    "meta",
+    "outbrain",
+    "promo",
+    "related",
+    "scroll",
+    "share",
+    "shoutbox",
+    "sidebar",
+    "skyscraper",
+    "sponsor",
+    "shopping",
+    "tags",
+    "tool",
+    "widget",
+];
+
+const BYLINE: &[&str] = &["byline", "author", "dateline", "writtenby", "p-author"];
+
+// Elements that make a <div> a container rather than a paragraph
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "div",
+    "dl",
+    "fieldset",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// The main content of an HTML document, without the navigation, headers, footers, banners
+/// and other boilerplate, found with an algorithm adapted from Mozilla's Readability.
+#[pyclass(frozen)]
+#[derive(Debug)]
+pub struct Article {
+    /// The title of the article: the OpenGraph or Twitter title, or the `<title>` without the
+    /// site name.
+    #[pyo3(get)]
+    pub title: Option<String>,
+    /// The author, from the `author` meta tag or the byline of the page.
+    #[pyo3(get)]
+    pub byline: Option<String>,
+    /// The HTML of the main content.
+    #[pyo3(get)]
+    pub html: String,
+}
+
+#[pymethods]
+impl Article {
+    /// The main content, converted to markdown with the default options of `to_markdown()`.
+    #[getter]
+    pub fn markdown(&self, py: Python) -> Result<String> {
+        py.allow_threads(|| to_markdown(&self.html, &MarkdownOptions::default()))
+    }
+
+    /// The main content, converted to plain text with the default options of `to_text()`.
+    #[getter]
+    fn text(&self, py: Python) -> Result<String> {
+        py.allow_threads(|| to_text(&self.html, &TextOptions::default()))
+    }
+
+    /// Converts the main content to markdown, with the options of `Response.to_markdown()`.
+    #[pyo3(
+        name = "to_markdown",
+        signature = (*, width=100, link_footnotes=true, image_alt=true, tables="borders")
+    )]
+    fn markdown_with(
+        &self,
+        py: Python,
+        width: usize,
+        link_footnotes: bool,
+        image_alt: bool,
+        tables: &str,
+    ) -> Result<String> {
+        let options = MarkdownOptions {
+            width,
+            link_footnotes,
+            image_alt,
+            tables: tables.parse()?,
+        };
+        py.allow_threads(|| to_markdown(&self.html, &options))
+    }
+
+    /// Converts the main content to plain text, with the options of `Response.to_text()`.
+    #[pyo3(name = "to_text", signature = (*, width=100, rich=false, tables="borders"))]
+    fn text_with(&self, py: Python, width: usize, rich: bool, tables: &str) -> Result<String> {
+        let options = TextOptions {
+            width,
+            rich,
+            tables: tables.parse()?,
+        };
+        py.allow_threads(|| to_text(&self.html, &options))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Article(title={:?}, byline={:?})", self.title, self.byline)
+    }
+}
+
+impl Article {
+    pub fn extract(doc: &Document, url: &str) -> Self {
+        let metadata = Metadata::extract(doc, url);
+        let title = metadata
+            .opengraph
+            .get("title")
+            .or(metadata.twitter.get("title"))
+            .map(|title| normalize_space(title))
+            .or(metadata.title.as_deref().map(clean_title))
+            .filter(|title| !title.is_empty());
+
+        // Class and id based removal can be too eager: retry without it if little is left
+        let mut extraction = Extraction::new(doc, true);
+        if extraction.text_len < 250 {
+            let relaxed = Extraction::new(doc, false);
+            if relaxed.text_len > extraction.text_len {
+                extraction = relaxed;
+            }
+        }
+        let byline = meta_author(doc).or(extraction.byline);
+        Article {
+            title,
+            byline,
+            html: extraction.html,
+        }
+    }
+}
+
+struct Extraction {
+    html: String,
+    text_len: usize,
+    byline: Option<String>,
+}
+
+impl Extraction {
+    fn new(doc: &Document, strip_unlikely: bool) -> Self {
+        let mut scorer = Scorer::new(doc, strip_unlikely);
+        let top = scorer.top_candidate();
+        let content = scorer.content(top);
+        let dropped = scorer.cleaned(&content);
+        let keep = |id: usize| !scorer.removed[id] && !dropped[id];
+        let mut html = String::new();
+        let mut text_len = 0;
+        for &id in &content {
+            html.push_str(&doc.outer_html_filtered(id, &keep));
+            text_len += scorer.text_len[id];
+        }
+        if content.len() > 1 {
+            html = format!("<div>{}</div>", html);
+        }
+        Extraction {
+            html,
+            text_len,
+            byline: scorer.byline.take(),
+        }
+    }
+}
+
+struct Scorer<'a> {
+    doc: &'a Document,
+    // Nodes left out of the content, with their descendants
+    removed: Vec<bool>,
+    // Length of the normalized text of the nodes, and of their links
+    text_len: Vec<usize>,
+    link_len: Vec<usize>,
+    commas: Vec<usize>,
+    scores: Vec<Option<f64>>,
+    byline: Option<String>,
+}
+
+impl<'a> Scorer<'a> {
+    fn new(doc: &'a Document, strip_unlikely: bool) -> Self {
+        let len = doc.descendants(Document::ROOT).end;
+        let mut scorer = Scorer {
+            doc,
+            removed: vec![false; len],
+            text_len: vec![0; len],
+            link_len: vec![0; len],
+            commas: vec![0; len],
+            scores: vec![None; len],
+            byline: None,
+        };
+        scorer.remove_boilerplate(strip_unlikely);
+        scorer.measure_text();
+        scorer.score_paragraphs();
+        scorer
+    }
+
+    fn remove_boilerplate(&mut self, strip_unlikely: bool) {
+        let doc = self.doc;
+        for id in doc.descendants(Document::ROOT) {
+            let parent = doc.node(id).parent.unwrap_or(Document::ROOT);
+            if self.removed[parent] {
+                self.removed[id] = true;
+                continue;
+            }
+            let Some(tag) = doc.name(id) else {
+                self.removed[id] = matches!(doc.node(id).data, NodeData::Comment(_));
+                continue;
+            };
+            if SKIPPED_TAGS.contains(&tag)
+                || doc.attr(id, "hidden").is_some()
+                || doc.attr(id, "aria-hidden") == Some("true")
+                || doc
+                    .attr(id, "role")
+                    .is_some_and(|role| SKIPPED_ROLES.contains(&role))
+            {
+                self.removed[id] = true;
+                continue;
+            }
+            let class_and_id = class_and_id(doc, id);
+            if self.byline.is_none() && is_byline(doc, id, &class_and_id) {
+                let byline = normalize_space(&doc.text(id));
+                if !byline.is_empty() && byline.chars().count() < 100 {
+                    self.byline = Some(byline);
+                    self.removed[id] = true;
+                    continue;
+                }
+            }
+            if strip_unlikely
+                && !matches!(
+                    tag,
+                    "a" | "article" | "body" | "html" | "main" | "table" | "code"
+                )
+                && contains_any(&class_and_id, UNLIKELY_CANDIDATE)
+                && !contains_any(&class_and_id, MAYBE_CANDIDATE)
+            {
+                self.removed[id] = true;
+            }
+        }
+    }
+
+    fn measure_text(&mut self) {
+        let doc = self.doc;
+        // Children come after their parent: sum the lengths from the end
+        for id in doc.descendants(Document::ROOT).rev() {
+            if self.removed[id] {
+                continue;
+            }
+            if let NodeData::Text(text) = &doc.node(id).data {
+                let text = normalize_space(text);
+                self.text_len[id] = text.chars().count();
+                self.commas[id] = text.matches(',').count();
+            }
+            if doc.name(id) == Some("a") {
+                self.link_len[id] = self.text_len[id];
+            }
+            if let Some(parent) = doc.node(id).parent {
+                self.text_len[parent] += self.text_len[id];
+                self.link_len[parent] += self.link_len[id];
+                self.commas[parent] += self.commas[id];
+            }
+        }
+    }
+
+    /// Scores the paragraphs, and adds their score to their ancestors, the candidates.
+    fn score_paragraphs(&mut self) {
+        let doc = self.doc;
+        for id in doc.descendants(Document::ROOT) {
+            if self.removed[id] || self.text_len[id] < 25 {
+                continue;
+            }
+            let is_paragraph = match doc.name(id) {
+                Some("p" | "pre" | "td" | "blockquote") => true,
+                Some("div" | "section") => !doc
+                    .element_children(id)
+                    .any(|child| doc.name(child).is_some_and(|tag| BLOCK_TAGS.contains(&tag))),
+                _ => false,
+            };
+            if !is_paragraph {
+                continue;
+            }
+            let score = 1.0 + self.commas[id] as f64 + (self.text_len[id] as f64 / 100.0).min(3.0);
+            let mut ancestor = doc.node(id).parent;
+            for divider in [1.0, 2.0, 6.0] {
+                let Some(candidate) = ancestor.filter(|&candidate| doc.is_element(candidate))
+                else {
+                    break;
+                };
+                let initial = self.initial_score(candidate);
+                *self.scores[candidate].get_or_insert(initial) += score / divider;
+                ancestor = doc.node(candidate).parent;
+            }
+        }
+    }
+
+    fn initial_score(&self, id: usize) -> f64 {
+        let tag_score = match self.doc.name(id) {
+            Some("div") => 5.0,
+            Some("pre" | "td" | "blockquote") => 3.0,
+            Some("address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form") => -3.0,
+            Some("h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th") => -5.0,
+            _ => 0.0,
+        };
+        tag_score + class_weight(self.doc, id)
+    }
+
+    fn link_density(&self, id: usize) -> f64 {
+        match self.text_len[id] {
+            0 => 0.0,
+            len => self.link_len[id] as f64 / len as f64,
+        }
+    }
+
+    /// Score of a candidate, penalized by its links.
+    fn final_score(&self, id: usize) -> Option<f64> {
+        self.scores[id].map(|score| score * (1.0 - self.link_density(id)))
+    }
+
+    fn top_candidate(&self) -> usize {
+        self.doc
+            .descendants(Document::ROOT)
+            .filter_map(|id| Some((id, self.final_score(id)?)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(id, _)| id)
+            .or_else(|| {
+                self.doc
+                    .descendants(Document::ROOT)
+                    .find(|&id| self.doc.name(id) == Some("body"))
+            })
+            .unwrap_or(Document::ROOT)
+    }
+
+    /// The top candidate, with its siblings that look like content too.
+    fn content(&self, top: usize) -> Vec<usize> {
+        let doc = self.doc;
+        let Some(parent) = doc
+            .node(top)
+            .parent
+            .filter(|&parent| doc.is_element(parent))
+        else {
+            return vec![top];
+        };
+        let top_score = self.final_score(top).unwrap_or(0.0);
+        let threshold = (top_score * 0.2).max(10.0);
+        let top_class = doc.attr(top, "class").unwrap_or_default();
+        doc.element_children(parent)
+            .filter(|&sibling| {
+                if sibling == top {
+                    return true;
+                }
+                if self.removed[sibling] {
+                    return false;
+                }
+                let bonus = match doc.attr(sibling, "class") {
+                    Some(class) if !top_class.is_empty() && class == top_class => top_score * 0.2,
+                    _ => 0.0,
+                };
+                if self
+                    .final_score(sibling)
+                    .is_some_and(|score| score + bonus >= threshold)
+                {
+                    return true;
+                }
+                if doc.name(sibling) != Some("p") {
+                    return false;
+                }
+                let (len, density) = (self.text_len[sibling], self.link_density(sibling));
+                match len {
+                    81.. => density < 0.25,
+                    1..=80 => density == 0.0 && normalize_space(&doc.text(sibling)).ends_with('.'),
+                    0 => false,
+                }
+            })
+            .collect()
+    }
+
+    /// The containers of the content that look like boilerplate: link lists, forms...
+    fn cleaned(&self, content: &[usize]) -> Vec<bool> {
+        let doc = self.doc;
+        let mut dropped = vec![false; self.removed.len()];
+        for &root in content {
+            for id in doc.descendants(root) {
+                if self.removed[id] {
+                    continue;
+                }
+                let Some(tag) = doc.name(id) else {
+                    continue;
+                };
+                let weight = class_weight(doc, id);
+                dropped[id] = match tag {
+                    "form" | "fieldset" | "table" | "ul" | "ol" | "div" | "section" => {
+                        weight + self.scores[id].unwrap_or(0.0) < 0.0
+                            || (self.link_density(id) > 0.5 && weight < 25.0)
+                    }
+                    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => weight < 0.0,
+                    _ => false,
+                };
+            }
+        }
+        dropped
+    }
+}
+
+/// The class and id of an element, lowercase.
+fn class_and_id(doc: &Document, id: usize) -> String {
+    let class = doc.attr(id, "class").unwrap_or_default();
+    let element_id = doc.attr(id, "id").unwrap_or_default();
+    format!("{} {}", class, element_id).to_ascii_lowercase()
+}
+
+fn contains_any(haystack: &str, needles: &[&str]) -> bool {
+    needles.iter().any(|needle| haystack.contains(needle))
+}
+
+/// +25 for each of the class and the id that look like content, -25 for boilerplate.
+fn class_weight(doc: &Document, id: usize) -> f64 {
+    let mut weight = 0.0;
+    for name in ["class", "id"] {
+        let Some(value) = doc.attr(id, name) else {
+            continue;
+        };
+        let value = value.to_ascii_lowercase();
+        if contains_any(&value, NEGATIVE) {
+            weight -= 25.0;
+        }
+        if contains_any(&value, POSITIVE) {
+            weight += 25.0;
+        }
+    }
+    weight
+}
+
+fn is_byline(doc: &Document, id: usize, class_and_id: &str) -> bool {
+    doc.attr(id, "rel") == Some("author")
+        || doc
+            .attr(id, "itemprop")
+            .is_some_and(|itemprop| itemprop.contains("author"))
+        || contains_any(class_and_id, BYLINE)
+}
+
+/// The content of the `author` meta tag.
+fn meta_author(doc: &Document) -> Option<String> {
+    doc.descendants(Document::ROOT)
+        .filter(|&id| doc.name(id) == Some("meta"))
+        .find(|&id| {
+            doc.attr(id, "name")
+                .or(doc.attr(id, "property"))
+                .is_some_and(|name| name.eq_ignore_ascii_case("author"))
+        })
+        .and_then(|id| doc.attr(id, "content"))
+        .map(normalize_space)
+        .filter(|author| !author.is_empty())
+}
+
+/// Removes the site name from a `<title>`, like "Headline | Site": keeps the part before the
+/// last separator, if it has at least 3 words.
+fn clean_title(title: &str) -> String {
+    let title = normalize_space(title);
+    for separator in [" | ", " - ", " – ", " — ", " :: ", " » ", " / "] {
+        if let Some(position) = title.rfind(separator) {
+            let head = title[..position].trim();
+            if head.split_whitespace().count() >= 3 {
+                return head.to_string();
+            }
+        }
+    }
+    title
+}
+
+#[cfg(test)]
+mod readability_tests {
+    use super::*;
+
+    const PARAGRAPH: &str =
+        "This is the article, a paragraph long enough to be scored, with a few commas, \
+        so that it clearly looks like the main content of the page.";
+
+    fn page() -> String {
+        format!(
+            r#"<html><head><title>The article headline | Example News</title>
+<meta name="author" content="Jane Doe"></head><body>
+<nav><a href="/">Home</a> <a href="/news">News</a></nav>
+<div class="cookie-banner"><p>We use cookies, to improve your experience, and for analytics purposes.</p></div>
+<div id="main"><article class="post-content"><h1>The article headline</h1>
+<p>{p}</p><p>{p}</p><p>{p}</p>
+<ul class="share-links"><li><a href="/a">Share on a social network, with a long link</a></li></ul>
+</article></div>
+<div class="sidebar"><p>{p}</p></div>
+<footer><p>Copyright, all rights reserved, and more legal text goes here.</p></footer>
+</body></html>"#,
+            p = PARAGRAPH
+        )
+    }
+
+    #[test]
+    fn test_extract_article() {
+        let doc = Document::parse(&page());
+        let article = Article::extract(&doc, "https://example.com/news/1");
+        assert_eq!(article.title.as_deref(), Some("The article headline"));
+        assert_eq!(article.byline.as_deref(), Some("Jane Doe"));
+        assert!(article
+            .html
+            .starts_with(r#"<article class="post-content">"#));
+        assert_eq!(article.html.matches(PARAGRAPH).count(), 3);
+        for boilerplate in ["Home", "cookies", "Share", "Copyright"] {
+            assert!(!article.html.contains(boilerplate), "{}", boilerplate);
+        }
+    }
+
+    #[test]
+    fn test_byline_element() {
+        let html = format!(
+            r#"<body><div><p class="byline">By John Smith</p><p>{p}</p><p>{p}</p></div></body>"#,
+            p = PARAGRAPH
+        );
+        let doc = Document::parse(&html);
+        let article = Article::extract(&doc, "https://example.com/");
+        assert_eq!(article.title, None);
+        assert_eq!(article.byline.as_deref(), Some("By John Smith"));
+        assert!(!article.html.contains("John Smith"));
+        assert_eq!(article.html.matches(PARAGRAPH).count(), 2);
+    }
+
+    #[test]
+    fn test_clean_title() {
+        assert_eq!(clean_title("A long headline - Site"), "A long headline");
+        assert_eq!(clean_title("Short - Site name"), "Short - Site name");
+        assert_eq!(clean_title("  No  separator "), "No separator");
+    }
+}
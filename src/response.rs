@@ -1,49 +1,311 @@
-use crate::utils::{get_encoding_from_content, get_encoding_from_headers};
+use crate::cookies::Cookie;
+use crate::exceptions::http_status_error;
+use crate::headers::Headers;
+use crate::html::{self, Document, Element};
+use crate::json::{loads, JsonOptions};
+use crate::links::{self, Link, LinkKind};
+use crate::markdown::{to_markdown, to_text, MarkdownOptions, TextOptions};
+use crate::metadata::Metadata;
+use crate::readability::Article;
+use crate::redirect::RedirectResponse;
+use crate::sse::EventsIterator;
+use crate::stream::{BytesIterator, LinesIterator, ResponseStream, StreamBody, TextIterator};
+use crate::timings::Timings;
+use crate::utils::{get_encoding_from_headers, guess_encoding, sniff_encoding};
+use std::sync::Arc;
+
 use anyhow::{anyhow, Result};
+use bytes::Bytes;
 use encoding_rs::Encoding;
 use foldhash::fast::RandomState;
-use html2text::{
-    from_read, from_read_with_decorator,
-    render::{RichDecorator, TrivialDecorator},
-};
 use indexmap::IndexMap;
 use pyo3::{prelude::*, types::PyBytes};
-use pythonize::pythonize;
-use serde_json::from_slice;
+use rquest::StatusCode;
 
 /// A struct representing an HTTP response.
 ///
 /// This struct provides methods to access various parts of an HTTP response, such as headers, cookies, status code, and the response body.
 /// It also supports decoding the response body as text or JSON, with the ability to specify the character encoding.
+/// Responses requested with `stream=True` read the body lazily, through `iter_bytes`, `iter_text`, `iter_lines` or `read`.
 #[pyclass]
 pub struct Response {
-    #[pyo3(get)]
-    pub content: Py<PyBytes>,
+    pub content: Option<Py<PyBytes>>,
+    pub stream: Option<ResponseStream>,
     #[pyo3(get)]
     pub cookies: IndexMap<String, String, RandomState>,
     #[pyo3(get, set)]
     pub encoding: String,
     #[pyo3(get)]
-    pub headers: IndexMap<String, String, RandomState>,
+    pub headers: Headers,
     #[pyo3(get)]
     pub status_code: u16,
     #[pyo3(get)]
     pub url: String,
+    pub method: String,
+    #[pyo3(get)]
+    pub timings: Timings,
+    /// Intermediate responses of the followed redirects, oldest first.
+    #[pyo3(get)]
+    pub history: Vec<RedirectResponse>,
+    /// The negotiated HTTP version: "HTTP/1.1", "HTTP/2"...
+    #[pyo3(get)]
+    pub http_version: &'static str,
+    /// The address of the server, or of the proxy, the response was received from.
+    #[pyo3(get)]
+    pub remote_addr: Option<String>,
+    /// The body parsed as HTML, on the first query.
+    pub document: Option<Arc<Document>>,
+}
+
+impl Response {
+    pub fn is_streaming(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// The body of a response that has already been read into memory.
+    pub fn buffered_content(&self, py: Python) -> Result<Py<PyBytes>> {
+        self.content
+            .as_ref()
+            .map(|content| content.clone_ref(py))
+            .ok_or_else(|| anyhow!("The response body has already been consumed or closed"))
+    }
+
+    /// Reads the next chunk of a streamed body. Returns `None` once the body is exhausted.
+    ///
+    /// The response is not borrowed while waiting for the chunk, so that other threads can use
+    /// it meanwhile.
+    pub fn next_stream_chunk(slf: &Bound<'_, Self>) -> Result<Option<Bytes>> {
+        let Some(body) = slf.borrow().stream.as_ref().map(ResponseStream::body) else {
+            return Ok(None);
+        };
+        let chunk = body.next_chunk(slf.py())?;
+        if chunk.is_none() {
+            slf.borrow_mut().end_stream(&body);
+        }
+        Ok(chunk)
+    }
+
+    /// Drops the stream reading `body`, once it is exhausted. Returns false if the stream has
+    /// been replaced or closed meanwhile.
+    fn end_stream(&mut self, body: &StreamBody) -> bool {
+        let current = self
+            .stream
+            .as_ref()
+            .is_some_and(|stream| stream.body().is(body));
+        if current {
+            self.stream = None;
+        }
+        current
+    }
+
+    /// Reads the rest of a streamed body into `content` and returns it, for the methods which
+    /// already borrow the response.
+    fn content(&mut self, py: Python) -> Result<Py<PyBytes>> {
+        if let Some(stream) = self.stream.take() {
+            let buf = stream.body().read_to_end(py)?;
+            self.content = Some(PyBytes::new(py, &buf).unbind());
+        }
+        self.buffered_content(py)
+    }
+
+    /// The body parsed as HTML. Parsed once, without holding the GIL, and cached.
+    fn document(&mut self, py: Python) -> Result<Arc<Document>> {
+        if let Some(document) = &self.document {
+            return Ok(Arc::clone(document));
+        }
+        let text = self.text(py)?;
+        let document = Arc::new(py.allow_threads(|| Document::parse(&text)));
+        self.document = Some(Arc::clone(&document));
+        Ok(document)
+    }
+
+    fn extract_links(
+        &mut self,
+        py: Python,
+        kind: LinkKind,
+        same_host: bool,
+        unique: bool,
+    ) -> Result<Vec<Link>> {
+        let document = self.document(py)?;
+        let url = &self.url;
+        Ok(py.allow_threads(|| links::extract(&document, url, kind, same_host, unique)))
+    }
+
+    /// Encoding used to decode a streamed body: detection from content is only possible
+    /// once the body has been read, otherwise the headers decide.
+    pub fn stream_encoding(&mut self, py: Python) -> Result<String> {
+        if self.content.is_some() {
+            return Ok(self.get_encoding(py)?.clone());
+        }
+        if !self.encoding.is_empty() {
+            return Ok(self.encoding.clone());
+        }
+        Ok(get_encoding_from_headers(&self.headers.to_indexmap())
+            .unwrap_or_else(|| "utf-8".to_string()))
+    }
 }
 
 #[pymethods]
 impl Response {
+    #[getter]
+    fn get_content(slf: &Bound<'_, Self>) -> Result<Py<PyBytes>> {
+        Self::read(slf)
+    }
+
+    /// Reads the rest of a streamed body into `content` and returns it.
+    ///
+    /// The response is not borrowed while waiting for the body, so that other threads can use
+    /// it meanwhile.
+    fn read(slf: &Bound<'_, Self>) -> Result<Py<PyBytes>> {
+        let py = slf.py();
+        let body = slf.borrow().stream.as_ref().map(ResponseStream::body);
+        if let Some(body) = body {
+            let buf = body.read_to_end(py)?;
+            let mut resp = slf.borrow_mut();
+            if resp.end_stream(&body) {
+                resp.content = Some(PyBytes::new(py, &buf).unbind());
+            }
+        }
+        slf.borrow().buffered_content(py)
+    }
+
+    /// Iterates over the body as bytes, in chunks of `chunk_size` bytes if given.
+    #[pyo3(signature = (chunk_size=None))]
+    fn iter_bytes(slf: &Bound<'_, Self>, chunk_size: Option<usize>) -> Result<BytesIterator> {
+        BytesIterator::new(slf, chunk_size)
+    }
+
+    /// Iterates over the body as text, decoded incrementally with the response encoding.
+    #[pyo3(signature = (chunk_size=None))]
+    fn iter_text(slf: &Bound<'_, Self>, chunk_size: Option<usize>) -> Result<TextIterator> {
+        TextIterator::new(slf, chunk_size)
+    }
+
+    /// Iterates over the lines of the body as text, without line endings.
+    fn iter_lines(slf: &Bound<'_, Self>) -> Result<LinesIterator> {
+        LinesIterator::new(slf)
+    }
+
+    /// Iterates over the events of a `text/event-stream` body, as `ServerSentEvent`s.
+    ///
+    /// * `reconnect` - Send the request again, with the `Last-Event-ID` header, when the stream
+    ///   ends or the connection is lost. Requires `stream=True`. `status_code` and `headers`
+    ///   remain those of the first response.
+    /// * `max_reconnects` - Maximum number of reconnections in a row without an event.
+    ///   Default is None, no limit.
+    #[pyo3(signature = (*, reconnect=false, max_reconnects=None))]
+    fn iter_events(
+        slf: &Bound<'_, Self>,
+        reconnect: bool,
+        max_reconnects: Option<usize>,
+    ) -> Result<EventsIterator> {
+        EventsIterator::new(slf, reconnect, max_reconnects)
+    }
+
+    /// Closes a streamed body, releasing the connection. The unread part of the body is discarded.
+    fn close(&mut self) {
+        self.stream = None;
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) {
+        self.close();
+    }
+
+    /// The cookies set by the response, with their attributes. `cookies` only keeps the values.
+    #[getter]
+    fn cookie_objects(&self) -> Vec<Cookie> {
+        Cookie::from_headers(self.headers.header_map())
+    }
+
+    /// Duration of the request in seconds, from sending it to reading the body
+    /// (to receiving the headers for streamed responses). See `timings` for the breakdown.
+    #[getter]
+    fn elapsed(&self) -> f64 {
+        self.timings.total
+    }
+
+    /// The reason phrase of the status code, e.g. "Not Found".
+    #[getter]
+    fn reason_phrase(&self) -> Option<&'static str> {
+        StatusCode::from_u16(self.status_code)
+            .ok()
+            .and_then(|status| status.canonical_reason())
+    }
+
+    #[getter]
+    fn is_success(&self) -> bool {
+        (200..300).contains(&self.status_code)
+    }
+
+    #[getter]
+    fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.status_code)
+    }
+
+    #[getter]
+    fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.status_code)
+    }
+
+    #[getter]
+    fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.status_code)
+    }
+
+    /// Raises `HTTPStatusError` if the status code is a client error (4xx) or a server error (5xx).
+    pub fn raise_for_status(slf: &Bound<'_, Self>) -> PyResult<()> {
+        let resp = slf.borrow();
+        if !resp.is_client_error() && !resp.is_server_error() {
+            return Ok(());
+        }
+        let kind = if resp.is_client_error() {
+            "Client error"
+        } else {
+            "Server error"
+        };
+        let msg = format!(
+            "{} '{} {}' for url '{}'",
+            kind,
+            resp.status_code,
+            resp.reason_phrase().unwrap_or_default(),
+            resp.url
+        );
+        Err(http_status_error(slf, &resp.method, &resp.url, msg))
+    }
+
     #[getter]
     fn get_encoding(&mut self, py: Python) -> Result<&String> {
         if !self.encoding.is_empty() {
             return Ok(&self.encoding);
         }
-        self.encoding = get_encoding_from_headers(&self.headers)
-            .or_else(|| get_encoding_from_content(self.content.as_bytes(py)))
-            .unwrap_or_else(|| "utf-8".to_string());
+        let content = self.content(py)?;
+        let raw_bytes = content.as_bytes(py);
+        let headers = self.headers.to_indexmap();
+        let url = &self.url;
+        self.encoding = py.allow_threads(|| sniff_encoding(&headers, raw_bytes, url));
         Ok(&self.encoding)
     }
 
+    /// The encoding guessed from the content alone, with `chardetng`, whatever the headers
+    /// and `<meta>` tags declare.
+    #[getter]
+    fn apparent_encoding(&mut self, py: Python) -> Result<String> {
+        let content = self.content(py)?;
+        let raw_bytes = content.as_bytes(py);
+        let url = &self.url;
+        let encoding = py.allow_threads(|| guess_encoding(raw_bytes, url));
+        Ok(encoding.name().to_ascii_lowercase())
+    }
+
     #[getter]
     fn text(&mut self, py: Python) -> Result<String> {
         // If self.encoding is empty, call get_encoding to populate self.encoding
@@ -52,7 +314,8 @@ impl Response {
         }
 
         // Convert Py<PyBytes> to &[u8]
-        let raw_bytes = self.content.as_bytes(py);
+        let content = self.content(py)?;
+        let raw_bytes = content.as_bytes(py);
 
         // Release the GIL here because decoding can be CPU-intensive
         py.allow_threads(|| {
@@ -69,32 +332,167 @@ impl Response {
         })
     }
 
-    fn json(&mut self, py: Python) -> Result<PyObject> {
-        let json_value: serde_json::Value = from_slice(self.content.as_bytes(py))?;
-        let result = pythonize(py, &json_value).unwrap().unbind();
-        Ok(result)
+    /// Decodes the body as JSON, building the Python objects directly.
+    ///
+    /// * `big_int` - Integers that don't fit in 64 bits are returned as exact `int`, instead of `float`.
+    /// * `decimal` - Numbers with a fraction or an exponent are returned as `decimal.Decimal`.
+    /// * `strict` - If False, accept control characters and lone surrogates in strings, `NaN`,
+    ///   `Infinity` and `-Infinity`, like Python's `json` module.
+    /// * `release_gil` - Parse without holding the GIL, into a compact intermediate form, letting
+    ///   other threads run. Slower for a single thread.
+    #[pyo3(signature = (*, big_int=false, decimal=false, strict=true, release_gil=false))]
+    fn json(
+        &mut self,
+        py: Python,
+        big_int: bool,
+        decimal: bool,
+        strict: bool,
+        release_gil: bool,
+    ) -> Result<PyObject> {
+        let content = self.content(py)?;
+        let options = JsonOptions {
+            big_int,
+            decimal,
+            strict,
+        };
+        loads(py, content.as_bytes(py), options, release_gil)
+    }
+
+    /// The elements matching the CSS selector, in document order.
+    fn select(&mut self, py: Python, css: &str) -> Result<Vec<Element>> {
+        html::select(&self.document(py)?, Document::ROOT, css)
+    }
+
+    /// The first element matching the CSS selector, or None.
+    fn select_one(&mut self, py: Python, css: &str) -> Result<Option<Element>> {
+        html::select_one(&self.document(py)?, Document::ROOT, css)
+    }
+
+    /// Evaluates an XPath 1.0 expression on the HTML document.
+    ///
+    /// Returns a list for node-sets, of `Element` for elements and of `str` for text and
+    /// attributes, and a `str`, `float` or `bool` for other results.
+    fn xpath(&mut self, py: Python, expr: &str) -> Result<PyObject> {
+        html::xpath(py, &self.document(py)?, Document::ROOT, expr)
+    }
+
+    /// The `<a>` and `<area>` links of the HTML document, as absolute URLs.
+    ///
+    /// URLs are resolved against the `<base href>` and the response URL, and only http and
+    /// https URLs are returned. With `same_host`, only the URLs on the host of the response URL
+    /// are returned, and with `unique`, only the first link to each URL.
+    #[pyo3(signature = (*, same_host=false, unique=true))]
+    fn links(&mut self, py: Python, same_host: bool, unique: bool) -> Result<Vec<Link>> {
+        self.extract_links(py, LinkKind::Links, same_host, unique)
+    }
+
+    /// The images of the HTML document: `<img>` sources and `srcset` candidates, and
+    /// `<picture>` sources. Same options as `links()`.
+    #[pyo3(signature = (*, same_host=false, unique=true))]
+    fn images(&mut self, py: Python, same_host: bool, unique: bool) -> Result<Vec<Link>> {
+        self.extract_links(py, LinkKind::Images, same_host, unique)
+    }
+
+    /// The resources loaded by the HTML document: images, scripts, stylesheets, icons,
+    /// preloads, media and frames. Same options as `links()`.
+    #[pyo3(signature = (*, same_host=false, unique=true))]
+    fn assets(&mut self, py: Python, same_host: bool, unique: bool) -> Result<Vec<Link>> {
+        self.extract_links(py, LinkKind::Assets, same_host, unique)
     }
 
+    /// Metadata of the HTML document: title, description, language, canonical URL, favicon,
+    /// OpenGraph and Twitter card fields, and JSON-LD objects.
+    #[getter]
+    fn metadata(&mut self, py: Python) -> Result<Metadata> {
+        let document = self.document(py)?;
+        let url = &self.url;
+        Ok(py.allow_threads(|| Metadata::extract(&document, url)))
+    }
+
+    /// The main content of the HTML document, without the navigation, headers, footers, banners
+    /// and other boilerplate, with its title and byline.
+    #[getter]
+    fn article(&mut self, py: Python) -> Result<Article> {
+        let document = self.document(py)?;
+        let url = &self.url;
+        Ok(py.allow_threads(|| Article::extract(&document, url)))
+    }
+
+    /// The main content of the HTML document, converted to markdown.
+    #[getter]
+    fn text_main(&mut self, py: Python) -> Result<String> {
+        self.article(py)?.markdown(py)
+    }
+
+    /// The HTML converted to markdown, with the default options of `to_markdown()`.
     #[getter]
     fn text_markdown(&mut self, py: Python) -> Result<String> {
-        let raw_bytes = self.content.bind(py).as_bytes();
-        let text = py.allow_threads(|| from_read(raw_bytes, 100))?;
-        Ok(text)
+        let text = self.text(py)?;
+        py.allow_threads(|| to_markdown(&text, &MarkdownOptions::default()))
     }
 
+    /// The HTML converted to plain text, with the default options of `to_text()`.
     #[getter]
     fn text_plain(&mut self, py: Python) -> Result<String> {
-        let raw_bytes = self.content.bind(py).as_bytes();
-        let text =
-            py.allow_threads(|| from_read_with_decorator(raw_bytes, 100, TrivialDecorator::new()))?;
-        Ok(text)
+        let text = self.text(py)?;
+        py.allow_threads(|| to_text(&text, &TextOptions::default()))
     }
 
+    /// The HTML converted to rich text, as `to_text(rich=True)`.
     #[getter]
     fn text_rich(&mut self, py: Python) -> Result<String> {
-        let raw_bytes = self.content.bind(py).as_bytes();
-        let text =
-            py.allow_threads(|| from_read_with_decorator(raw_bytes, 100, RichDecorator::new()))?;
-        Ok(text)
+        let text = self.text(py)?;
+        let options = TextOptions {
+            rich: true,
+            ..Default::default()
+        };
+        py.allow_threads(|| to_text(&text, &options))
+    }
+
+    /// Converts the HTML to markdown. The body is decoded like `text`.
+    ///
+    /// * `width` - The width lines are wrapped at.
+    /// * `link_footnotes` - Links as `[text][1]` with a list of `[1]: url` footnotes at the end.
+    ///   If False, links are inlined as `[text](url)`.
+    /// * `image_alt` - Images as their `[alt text]`. If False, images are dropped.
+    /// * `tables` - "borders" to draw the table borders, "no_borders" to align the cells
+    ///   without borders, or "raw" to put each cell on its own lines.
+    #[pyo3(
+        name = "to_markdown",
+        signature = (*, width=100, link_footnotes=true, image_alt=true, tables="borders")
+    )]
+    fn markdown(
+        &mut self,
+        py: Python,
+        width: usize,
+        link_footnotes: bool,
+        image_alt: bool,
+        tables: &str,
+    ) -> Result<String> {
+        let options = MarkdownOptions {
+            width,
+            link_footnotes,
+            image_alt,
+            tables: tables.parse()?,
+        };
+        let text = self.text(py)?;
+        py.allow_threads(|| to_markdown(&text, &options))
+    }
+
+    /// Converts the HTML to plain text. The body is decoded like `text`.
+    ///
+    /// * `width` - The width lines are wrapped at.
+    /// * `rich` - Emphasis, links and images marked up like `*this*`, as in `text_rich`.
+    /// * `tables` - "borders" to draw the table borders, "no_borders" to align the cells
+    ///   without borders, or "raw" to put each cell on its own lines.
+    #[pyo3(name = "to_text", signature = (*, width=100, rich=false, tables="borders"))]
+    fn text_with(&mut self, py: Python, width: usize, rich: bool, tables: &str) -> Result<String> {
+        let options = TextOptions {
+            width,
+            rich,
+            tables: tables.parse()?,
+        };
+        let text = self.text(py)?;
+        py.allow_threads(|| to_text(&text, &options))
     }
 }
@@ -0,0 +1,350 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use pyo3::prelude::*;
+use rquest::{
+    header::{HeaderMap, RETRY_AFTER},
+    Method, RequestBuilder, Response,
+};
+
+use crate::limiter::HostLimit;
+use crate::utils::parse_retry_after;
+
+/// Retry policy for failed requests, with exponential backoff.
+///
+/// The delay before retry `n` is `backoff_base * 2^(n-1)` seconds, capped at `backoff_max`,
+/// plus a random jitter of up to `jitter` seconds. A `Retry-After` header on a retried response
+/// replaces the computed delay. It is capped at `backoff_max` too: a longer `Retry-After` is
+/// waited for `backoff_max` only, so that a server can't stall the client, and the request is
+/// retried then, even if the server may still be unavailable.
+#[pyclass(frozen)]
+#[derive(Clone, Debug)]
+pub struct Retry {
+    /// Maximum number of attempts, the first one included.
+    #[pyo3(get)]
+    pub max_attempts: u32,
+    /// Status codes of the responses to retry.
+    #[pyo3(get)]
+    pub status_codes: Vec<u16>,
+    /// Whether connect errors are retried.
+    #[pyo3(get)]
+    pub retry_connect: bool,
+    /// Whether timeouts are retried.
+    #[pyo3(get)]
+    pub retry_timeout: bool,
+    /// Base of the exponential backoff, in seconds.
+    #[pyo3(get)]
+    pub backoff_base: f64,
+    /// Maximum delay between two attempts, in seconds.
+    #[pyo3(get)]
+    pub backoff_max: f64,
+    /// Maximum random delay added to the backoff, in seconds.
+    #[pyo3(get)]
+    pub jitter: f64,
+    /// Whether to honor the `Retry-After` header.
+    #[pyo3(get)]
+    pub respect_retry_after: bool,
+    /// Whether non-idempotent methods (POST, PATCH) are retried too.
+    #[pyo3(get)]
+    pub retry_non_idempotent: bool,
+}
+
+#[pymethods]
+impl Retry {
+    #[new]
+    #[pyo3(signature = (max_attempts=3, status_codes=vec![429, 502, 503, 504], retry_connect=true,
+        retry_timeout=true, backoff_base=0.5, backoff_max=30.0, jitter=0.1, respect_retry_after=true,
+        retry_non_idempotent=false))]
+    fn new(
+        max_attempts: u32,
+        status_codes: Vec<u16>,
+        retry_connect: bool,
+        retry_timeout: bool,
+        backoff_base: f64,
+        backoff_max: f64,
+        jitter: f64,
+        respect_retry_after: bool,
+        retry_non_idempotent: bool,
+    ) -> Result<Self> {
+        if max_attempts == 0 {
+            return Err(anyhow!("max_attempts must be greater than 0"));
+        }
+        for (name, value) in [
+            ("backoff_base", backoff_base),
+            ("backoff_max", backoff_max),
+            ("jitter", jitter),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(anyhow!("{} must be a non-negative number", name));
+            }
+        }
+        Ok(Retry {
+            max_attempts,
+            status_codes,
+            retry_connect,
+            retry_timeout,
+            backoff_base,
+            backoff_max,
+            jitter,
+            respect_retry_after,
+            retry_non_idempotent,
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Retry(max_attempts={}, status_codes={:?}, retry_connect={}, retry_timeout={}, \
+            backoff_base={}, backoff_max={}, jitter={}, respect_retry_after={}, retry_non_idempotent={})",
+            self.max_attempts,
+            self.status_codes,
+            self.retry_connect,
+            self.retry_timeout,
+            self.backoff_base,
+            self.backoff_max,
+            self.jitter,
+            self.respect_retry_after,
+            self.retry_non_idempotent
+        )
+    }
+}
+
+/// The `retries` argument: a number of retries or a `Retry` policy.
+#[derive(FromPyObject)]
+pub enum RetryArg {
+    Policy(Retry),
+    Count(u32),
+}
+
+impl From<RetryArg> for Retry {
+    fn from(arg: RetryArg) -> Self {
+        match arg {
+            RetryArg::Policy(retry) => retry,
+            RetryArg::Count(retries) => Retry {
+                max_attempts: retries.saturating_add(1),
+                ..Retry::default()
+            },
+        }
+    }
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            max_attempts: 3,
+            status_codes: vec![429, 502, 503, 504],
+            retry_connect: true,
+            retry_timeout: true,
+            backoff_base: 0.5,
+            backoff_max: 30.0,
+            jitter: 0.1,
+            respect_retry_after: true,
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl Retry {
+    /// Whether requests with `method` may be retried.
+    pub fn allows(&self, method: &Method) -> bool {
+        self.max_attempts > 1
+            && (self.retry_non_idempotent
+                || matches!(
+                    *method,
+                    Method::GET
+                        | Method::HEAD
+                        | Method::OPTIONS
+                        | Method::PUT
+                        | Method::DELETE
+                        | Method::TRACE
+                ))
+    }
+
+    fn should_retry_error(&self, err: &rquest::Error) -> bool {
+        (self.retry_timeout && err.is_timeout()) || (self.retry_connect && err.is_connect())
+    }
+
+    /// Backoff before retry number `retry`, starting at 1.
+    fn backoff(&self, retry: u32) -> Duration {
+        let exp = self.backoff_base * 2f64.powi(retry.saturating_sub(1).min(63) as i32);
+        let delay = exp.min(self.backoff_max) + rand::random::<f64>() * self.jitter;
+        Duration::from_secs_f64(delay)
+    }
+
+    /// Delay before retrying a response with `status` and `headers`, received at `now`,
+    /// or None if it must not be retried.
+    fn response_delay(
+        &self,
+        status: u16,
+        headers: &HeaderMap,
+        retry: u32,
+        now: SystemTime,
+    ) -> Option<Duration> {
+        if !self.status_codes.contains(&status) {
+            return None;
+        }
+        let retry_after = headers
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| parse_retry_after(v, now));
+        match retry_after {
+            Some(delay) if self.respect_retry_after => {
+                Some(delay.min(Duration::from_secs_f64(self.backoff_max)))
+            }
+            _ => Some(self.backoff(retry)),
+        }
+    }
+}
+
+/// Sends the request, retrying it according to `retry`. Retries wait for the rate limit
+/// of the host too, if any.
+///
+/// Requests with a streamed body can't be cloned, so they are sent once.
+pub async fn send_with_retry(
+    request_builder: RequestBuilder,
+    retry: Option<Retry>,
+    limit: Option<Arc<HostLimit>>,
+) -> Result<Response> {
+    let Some(retry) = retry else {
+        return Ok(request_builder.send().await?);
+    };
+    let mut attempt = 1;
+    loop {
+        let Some(attempt_builder) = request_builder.try_clone() else {
+            return Ok(request_builder.send().await?);
+        };
+        if attempt > 1 {
+            if let Some(limit) = &limit {
+                limit.wait_rate().await;
+            }
+        }
+        let result = attempt_builder.send().await;
+        if attempt >= retry.max_attempts {
+            return Ok(result?);
+        }
+        let delay = match &result {
+            Ok(resp) => retry.response_delay(
+                resp.status().as_u16(),
+                resp.headers(),
+                attempt,
+                SystemTime::now(),
+            ),
+            Err(err) if retry.should_retry_error(err) => Some(retry.backoff(attempt)),
+            Err(_) => None,
+        };
+        let Some(delay) = delay else {
+            return Ok(result?);
+        };
+        match &result {
+            Ok(resp) => tracing::info!(
+                "retry: {} {} attempt {} in {:?}",
+                resp.url(),
+                resp.status().as_u16(),
+                attempt + 1,
+                delay
+            ),
+            Err(err) => tracing::info!("retry: {} attempt {} in {:?}", err, attempt + 1, delay),
+        }
+        // Drop the failed response before waiting, releasing its connection
+        drop(result);
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod retry_tests {
+    use super::*;
+    use rquest::header::HeaderValue;
+
+    fn policy() -> Retry {
+        Retry {
+            backoff_base: 0.5,
+            backoff_max: 3.0,
+            jitter: 0.0,
+            ..Retry::default()
+        }
+    }
+
+    fn retry_after(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_backoff() {
+        let retry = policy();
+        let delays: Vec<f64> = (1..=5).map(|n| retry.backoff(n).as_secs_f64()).collect();
+        assert_eq!(delays, vec![0.5, 1.0, 2.0, 3.0, 3.0]);
+        assert_eq!(retry.backoff(1000), Duration::from_secs(3));
+
+        let retry = Retry {
+            jitter: 0.25,
+            ..policy()
+        };
+        for _ in 0..100 {
+            let delay = retry.backoff(2).as_secs_f64();
+            assert!((1.0..1.25).contains(&delay), "{}", delay);
+        }
+    }
+
+    #[test]
+    fn test_response_delay() {
+        let retry = policy();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let delay = |status, headers: &HeaderMap, n| retry.response_delay(status, headers, n, now);
+        let none = HeaderMap::new();
+
+        assert_eq!(delay(404, &none, 1), None);
+        assert_eq!(delay(503, &none, 1), Some(Duration::from_millis(500)));
+        assert_eq!(delay(503, &none, 3), Some(Duration::from_secs(2)));
+        // Retry-After replaces the backoff, capped at backoff_max
+        assert_eq!(
+            delay(429, &retry_after("2"), 1),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            delay(429, &retry_after("60"), 1),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(
+            delay(429, &retry_after("Tue, 14 Nov 2023 22:13:21 GMT"), 1),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            delay(429, &retry_after("soon"), 2),
+            Some(Duration::from_secs(1))
+        );
+
+        let retry = Retry {
+            respect_retry_after: false,
+            ..policy()
+        };
+        assert_eq!(
+            retry.response_delay(429, &retry_after("60"), 1, now),
+            Some(Duration::from_millis(500))
+        );
+    }
+
+    #[test]
+    fn test_allows() {
+        let retry = policy();
+        assert!(retry.allows(&Method::GET));
+        assert!(retry.allows(&Method::PUT));
+        assert!(!retry.allows(&Method::POST));
+        assert!(!retry.allows(&Method::PATCH));
+
+        let retry = Retry {
+            retry_non_idempotent: true,
+            ..policy()
+        };
+        assert!(retry.allows(&Method::POST));
+
+        let retry = Retry::from(RetryArg::Count(0));
+        assert_eq!(retry.max_attempts, 1);
+        assert!(!retry.allows(&Method::GET));
+        let retry = Retry::from(RetryArg::Count(u32::MAX));
+        assert_eq!(retry.max_attempts, u32::MAX);
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use anyhow::{anyhow, Result};
+use tokio::runtime::{self, Handle, Runtime};
+
+// Tokio global one-thread runtime. A background thread blocks on it forever, so that the tasks
+// spawned on it, such as the connections of the pool, make progress outside of the `block_on`
+// calls of the requests too. Those still poll their future on the calling thread.
+static RUNTIME: LazyLock<&'static Runtime> = LazyLock::new(|| {
+    let rt: &'static Runtime = Box::leak(Box::new(
+        runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap(),
+    ));
+    std::thread::Builder::new()
+        .name("primp-runtime".to_string())
+        .spawn(move || rt.block_on(std::future::pending::<()>()))
+        .unwrap();
+    rt
+});
+
+// Tokio global multi-thread runtimes, one per requested number of worker threads
+static MULTI_THREAD_RUNTIMES: LazyLock<Mutex<HashMap<usize, &'static Runtime>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Builds a multi-thread runtime with `worker_threads` workers.
+fn build_multi_thread_runtime(worker_threads: usize) -> Result<Runtime> {
+    Ok(runtime::Builder::new_multi_thread()
+        .worker_threads(worker_threads)
+        .thread_name("primp-worker")
+        .enable_all()
+        .build()?)
+}
+
+/// Returns the shared multi-thread runtime with `worker_threads` workers, creating it on first use.
+fn shared_multi_thread_runtime(worker_threads: usize) -> Result<&'static Runtime> {
+    let mut runtimes = MULTI_THREAD_RUNTIMES.lock().unwrap();
+    if let Some(rt) = runtimes.get(&worker_threads) {
+        return Ok(rt);
+    }
+    let rt: &'static Runtime = Box::leak(Box::new(build_multi_thread_runtime(worker_threads)?));
+    runtimes.insert(worker_threads, rt);
+    Ok(rt)
+}
+
+/// Default number of worker threads: env var PRIMP_WORKER_THREADS or the number of available CPUs.
+fn default_worker_threads() -> usize {
+    std::env::var("PRIMP_WORKER_THREADS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        })
+}
+
+/// The Tokio runtime flavour a client executes its requests on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuntimeMode {
+    /// Global single-threaded runtime shared by all clients (default).
+    CurrentThread,
+    /// Global multi-threaded runtime shared by all clients with the same number of workers.
+    MultiThread,
+    /// Multi-threaded runtime owned by a single client.
+    Dedicated,
+}
+
+impl FromStr for RuntimeMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "current_thread" => Ok(RuntimeMode::CurrentThread),
+            "multi_thread" => Ok(RuntimeMode::MultiThread),
+            "dedicated" => Ok(RuntimeMode::Dedicated),
+            _ => Err(anyhow!("Invalid runtime: {:?}", s)),
+        }
+    }
+}
+
+impl RuntimeMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuntimeMode::CurrentThread => "current_thread",
+            RuntimeMode::MultiThread => "multi_thread",
+            RuntimeMode::Dedicated => "dedicated",
+        }
+    }
+}
+
+/// Runtime owned by a client. Shuts down without blocking when the last reference is dropped,
+/// since that may happen on a worker thread or while the GIL is held.
+struct OwnedRuntime(Option<Runtime>);
+
+impl Drop for OwnedRuntime {
+    fn drop(&mut self) {
+        if let Some(rt) = self.0.take() {
+            rt.shutdown_background();
+        }
+    }
+}
+
+#[derive(Clone)]
+enum RuntimeRef {
+    Shared(&'static Runtime),
+    Owned(Arc<OwnedRuntime>),
+}
+
+/// The runtime used by an `RClient`, selected at construction.
+#[derive(Clone)]
+pub struct ClientRuntime {
+    mode: RuntimeMode,
+    worker_threads: Option<usize>,
+    runtime: RuntimeRef,
+}
+
+impl ClientRuntime {
+    /// Creates the client runtime.
+    ///
+    /// `mode` falls back to env var PRIMP_RUNTIME, then to `current_thread`.
+    /// `worker_threads` falls back to env var PRIMP_WORKER_THREADS, then to the number of CPUs,
+    /// and is ignored in `current_thread` mode.
+    pub fn new(mode: Option<String>, worker_threads: Option<usize>) -> Result<Self> {
+        let mode = match mode.or_else(|| std::env::var("PRIMP_RUNTIME").ok()) {
+            Some(mode) => mode.parse()?,
+            None => RuntimeMode::CurrentThread,
+        };
+        if worker_threads == Some(0) {
+            return Err(anyhow!("worker_threads must be greater than 0"));
+        }
+
+        let (worker_threads, runtime) = match mode {
+            RuntimeMode::CurrentThread => (None, RuntimeRef::Shared(*RUNTIME)),
+            RuntimeMode::MultiThread => {
+                let n = worker_threads.unwrap_or_else(default_worker_threads);
+                (Some(n), RuntimeRef::Shared(shared_multi_thread_runtime(n)?))
+            }
+            RuntimeMode::Dedicated => {
+                let n = worker_threads.unwrap_or_else(default_worker_threads);
+                let rt = build_multi_thread_runtime(n)?;
+                (Some(n), RuntimeRef::Owned(Arc::new(OwnedRuntime(Some(rt)))))
+            }
+        };
+
+        Ok(ClientRuntime {
+            mode,
+            worker_threads,
+            runtime,
+        })
+    }
+
+    pub fn mode(&self) -> RuntimeMode {
+        self.mode
+    }
+
+    pub fn worker_threads(&self) -> Option<usize> {
+        self.worker_threads
+    }
+
+    fn runtime(&self) -> &Runtime {
+        match &self.runtime {
+            RuntimeRef::Shared(rt) => rt,
+            RuntimeRef::Owned(rt) => rt.0.as_ref().unwrap(),
+        }
+    }
+
+    /// Blocks the current thread on `future`. Must be called with the GIL released.
+    ///
+    /// Runtimes with worker threads run the future on them, decoding included, so that the
+    /// calling thread only waits for its output.
+    pub fn block_on<F>(&self, future: F) -> F::Output
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let runtime = self.runtime();
+        match self.mode {
+            RuntimeMode::CurrentThread => runtime.block_on(future),
+            _ => {
+                let output = runtime.block_on(runtime.spawn(future));
+                // The runtime outlives `self`, so the task can only fail by panicking
+                output.unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+            }
+        }
+    }
+
+    /// Handle to spawn the futures awaited from Python's event loop on.
+    ///
+    /// Async requests run on the client runtime, like the sync ones, because the connections of
+    /// the pool are driven by the runtime that opened them: a connection opened on one runtime
+    /// and reused from another would stall whenever the first one is idle.
+    pub fn handle(&self) -> Handle {
+        self.runtime().handle().clone()
+    }
+}
+
+/// Awaits `future` spawned on the runtime behind `handle`.
+pub async fn run_on<F>(handle: Handle, future: F) -> Result<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    Ok(handle.spawn(future).await?)
+}
+
+#[cfg(test)]
+mod runtime_tests {
+    use super::*;
+
+    fn thread_name() -> Option<String> {
+        std::thread::current().name().map(String::from)
+    }
+
+    #[test]
+    fn test_block_on_worker_threads() {
+        let caller = thread_name();
+        let runtime = ClientRuntime::new(Some("current_thread".to_string()), None).unwrap();
+        assert_eq!(runtime.block_on(async { thread_name() }), caller);
+        for mode in ["multi_thread", "dedicated"] {
+            let runtime = ClientRuntime::new(Some(mode.to_string()), Some(2)).unwrap();
+            assert_eq!(runtime.mode().as_str(), mode);
+            let name = runtime.block_on(async { thread_name() });
+            assert_eq!(name.as_deref(), Some("primp-worker"), "{}", mode);
+        }
+    }
+
+    #[test]
+    fn test_current_thread_spawned_tasks_progress() {
+        // A task spawned on the current-thread runtime, like a pooled connection, keeps running
+        // after `block_on` returns
+        let runtime = ClientRuntime::new(Some("current_thread".to_string()), None).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        runtime.block_on(async move {
+            tokio::spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                tx.send(thread_name()).unwrap();
+            });
+        });
+        let name = rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        assert_eq!(name.as_deref(), Some("primp-runtime"));
+    }
+}
@@ -0,0 +1,324 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use pyo3::prelude::*;
+use rquest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+
+use crate::json::{loads, JsonOptions};
+use crate::response::Response;
+use crate::stream::{LineReader, Replay};
+
+/// Delay before reconnecting, until the server sets one with a `retry` field.
+const DEFAULT_RETRY: Duration = Duration::from_secs(3);
+
+const LAST_EVENT_ID: HeaderName = HeaderName::from_static("last-event-id");
+
+/// An event of a `text/event-stream` body.
+#[pyclass(frozen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerSentEvent {
+    /// The event type, "message" if the event has none.
+    #[pyo3(get)]
+    pub event: String,
+    /// The `data` lines of the event, joined with "\n".
+    #[pyo3(get)]
+    pub data: String,
+    /// The last event ID of the stream, set by this event or a previous one.
+    #[pyo3(get)]
+    pub id: Option<String>,
+    /// The reconnection time set by the event, in milliseconds.
+    #[pyo3(get)]
+    pub retry: Option<u64>,
+}
+
+#[pymethods]
+impl ServerSentEvent {
+    /// Parses the data of the event as JSON.
+    fn json(&self, py: Python) -> Result<PyObject> {
+        let options = JsonOptions {
+            big_int: false,
+            decimal: false,
+            strict: true,
+        };
+        loads(py, self.data.as_bytes(), options, false)
+    }
+
+    fn __repr__(&self) -> String {
+        let mut repr = format!(
+            "ServerSentEvent(event={:?}, data={:?}",
+            self.event, self.data
+        );
+        if let Some(id) = &self.id {
+            repr.push_str(&format!(", id={:?}", id));
+        }
+        if let Some(retry) = self.retry {
+            repr.push_str(&format!(", retry={}", retry));
+        }
+        repr.push(')');
+        repr
+    }
+}
+
+/// Parser of the `text/event-stream` format, fed line by line, as specified by the
+/// HTML standard.
+#[derive(Debug, Default)]
+pub struct EventParser {
+    // Fields of the event being parsed
+    event: String,
+    data: String,
+    retry: Option<u64>,
+    // Kept from an event to the next
+    last_event_id: String,
+    reconnection_time: Option<u64>,
+}
+
+impl EventParser {
+    /// Parses a line, without its ending. Returns the event ended by an empty line.
+    pub fn line(&mut self, line: &str) -> Option<ServerSentEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            // A comment, often sent as a keep-alive
+            "" => {}
+            "event" => self.event = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = value.to_string(),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                let retry = value.parse().ok();
+                self.retry = retry;
+                self.reconnection_time = retry;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<ServerSentEvent> {
+        let event = std::mem::take(&mut self.event);
+        let mut data = std::mem::take(&mut self.data);
+        let retry = self.retry.take();
+        // Events without data are not dispatched
+        if data.is_empty() {
+            return None;
+        }
+        data.pop();
+        Some(ServerSentEvent {
+            event: match event.is_empty() {
+                true => "message".to_string(),
+                false => event,
+            },
+            data,
+            id: Some(self.last_event_id.clone()).filter(|id| !id.is_empty()),
+            retry,
+        })
+    }
+
+    /// Drops the event being parsed, when the stream ends before it does.
+    pub fn discard_event(&mut self) {
+        self.event.clear();
+        self.data.clear();
+        self.retry = None;
+    }
+
+    pub fn last_event_id(&self) -> &str {
+        &self.last_event_id
+    }
+
+    /// The last reconnection time set by the server, in milliseconds.
+    pub fn reconnection_time(&self) -> Option<u64> {
+        self.reconnection_time
+    }
+}
+
+/// Iterator over the events of a `text/event-stream` body.
+///
+/// With `reconnect`, the request is sent again when the stream ends or the connection is lost,
+/// with the `Last-Event-ID` header, after the reconnection time set by the server (3 seconds by
+/// default). The iteration stops if the server answers with another status than 200 or another
+/// content type than `text/event-stream`, or after `max_reconnects` reconnections in a row
+/// without an event.
+#[pyclass]
+pub struct EventsIterator {
+    response: Py<Response>,
+    lines: LineReader,
+    parser: EventParser,
+    replay: Option<Replay>,
+    max_reconnects: Option<usize>,
+    reconnects: usize,
+}
+
+impl EventsIterator {
+    pub fn new(
+        response: &Bound<'_, Response>,
+        reconnect: bool,
+        max_reconnects: Option<usize>,
+    ) -> Result<Self> {
+        let replay = match reconnect {
+            true => {
+                let replay = response.borrow().stream.as_ref().and_then(|s| s.replay());
+                let replay = replay.ok_or_else(|| {
+                    anyhow!("reconnect requires stream=True and a request body that can be resent")
+                })?;
+                Some(replay)
+            }
+            false => None,
+        };
+        Ok(EventsIterator {
+            response: response.clone().unbind(),
+            lines: LineReader::utf8(response)?,
+            parser: EventParser::default(),
+            replay,
+            max_reconnects,
+            reconnects: 0,
+        })
+    }
+
+    /// Sends the request again, once the stream has ended. Returns false if the iteration
+    /// is over.
+    fn reconnect(&mut self, py: Python) -> Result<bool> {
+        let Some(replay) = &self.replay else {
+            return Ok(false);
+        };
+        self.parser.discard_event();
+        let mut headers = HeaderMap::new();
+        if !self.parser.last_event_id().is_empty() {
+            headers.insert(
+                LAST_EVENT_ID,
+                HeaderValue::from_str(self.parser.last_event_id())?,
+            );
+        }
+        let delay = self
+            .parser
+            .reconnection_time()
+            .map_or(DEFAULT_RETRY, Duration::from_millis);
+        let mut last_error = None;
+        while self.max_reconnects.is_none_or(|max| self.reconnects < max) {
+            self.reconnects += 1;
+            tracing::debug!("event stream: reconnecting in {:?}", delay);
+            match replay.send(py, delay, headers.clone()) {
+                Ok((200, headers, stream)) if is_event_stream(&headers) => {
+                    self.response.borrow_mut(py).stream = Some(stream);
+                    self.lines = LineReader::utf8(self.response.bind(py))?;
+                    return Ok(true);
+                }
+                Ok((status_code, _, _)) => {
+                    tracing::debug!("event stream: closed by the server ({})", status_code);
+                    return Ok(false);
+                }
+                Err(err) => {
+                    tracing::debug!("event stream: reconnection failed: {:#}", err);
+                    last_error = Some(err);
+                }
+            }
+        }
+        last_error.map_or(Ok(false), Err)
+    }
+}
+
+#[pymethods]
+impl EventsIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> Result<Option<ServerSentEvent>> {
+        loop {
+            let line = match self.lines.next_line(py) {
+                Ok(Some(line)) => line,
+                Ok(None) => match self.reconnect(py)? {
+                    true => continue,
+                    false => return Ok(None),
+                },
+                Err(err) if self.replay.is_some() => {
+                    tracing::debug!("event stream: connection lost: {:#}", err);
+                    match self.reconnect(py)? {
+                        true => continue,
+                        false => return Err(err),
+                    }
+                }
+                Err(err) => return Err(err),
+            };
+            if let Some(event) = self.parser.line(&line) {
+                self.reconnects = 0;
+                return Ok(Some(event));
+            }
+        }
+    }
+}
+
+fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/event-stream"))
+}
+
+#[cfg(test)]
+mod sse_tests {
+    use super::*;
+
+    fn parse(lines: &[&str]) -> Vec<ServerSentEvent> {
+        let mut parser = EventParser::default();
+        lines.iter().filter_map(|line| parser.line(line)).collect()
+    }
+
+    fn event(event: &str, data: &str, id: Option<&str>, retry: Option<u64>) -> ServerSentEvent {
+        ServerSentEvent {
+            event: event.to_string(),
+            data: data.to_string(),
+            id: id.map(str::to_string),
+            retry,
+        }
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let events = parse(&[
+            ": keep-alive",
+            "data: first",
+            "",
+            "event: update",
+            "data:{\"a\": 1}",
+            "data:  indented",
+            "data",
+            "id: 7",
+            "",
+            "data: after",
+            "retry: 1500",
+            "",
+        ]);
+        assert_eq!(
+            events,
+            vec![
+                event("message", "first", None, None),
+                event("update", "{\"a\": 1}\n indented\n", Some("7"), None),
+                event("message", "after", Some("7"), Some(1500)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ignored_fields() {
+        let mut parser = EventParser::default();
+        for line in ["event: ping", "id: 1", "retry: soon", "unknown: x", ""] {
+            assert_eq!(parser.line(line), None);
+        }
+        assert_eq!(parser.last_event_id(), "1");
+        assert_eq!(parser.reconnection_time(), None);
+        parser.line("data: partial");
+        parser.discard_event();
+        assert_eq!(parser.line(""), None);
+        parser.line("id");
+        parser.line("data: x");
+        assert_eq!(parser.line(""), Some(event("message", "x", None, None)));
+    }
+}
@@ -0,0 +1,471 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use bytes::{Bytes, BytesMut};
+use encoding_rs::{Decoder, Encoding};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use rquest::header::HeaderMap;
+use rquest::RequestBuilder;
+use tokio::sync::OwnedSemaphorePermit;
+
+use crate::decoder::{BodyDecoder, BodyLimits, BodyReader};
+use crate::exceptions::request_error;
+use crate::limiter::HostLimiter;
+use crate::redirect::{send_following_redirects, Sent};
+use crate::response::Response;
+use crate::retry::send_with_retry;
+use crate::runtime::ClientRuntime;
+
+/// Unread body of a response requested with `stream=True`.
+pub struct ResponseStream {
+    body: StreamBody,
+    // Concurrency slot of the host, held until the stream is dropped
+    _permit: Option<OwnedSemaphorePermit>,
+    replay: Option<Replay>,
+}
+
+/// Handle on the unread body of a stream, read without borrowing the `Response`, so that other
+/// threads can use the response while the GIL is released.
+#[derive(Clone)]
+pub struct StreamBody {
+    // rquest::Response is not Sync, which pyclasses must be. Shared with the reads running on
+    // the runtime, one at a time.
+    reader: Arc<tokio::sync::Mutex<BodyReader>>,
+    url: String,
+    method: String,
+    runtime: ClientRuntime,
+}
+
+impl ResponseStream {
+    pub fn new(
+        body: BodyReader,
+        method: String,
+        runtime: ClientRuntime,
+        permit: Option<OwnedSemaphorePermit>,
+        replay: Option<Replay>,
+    ) -> Self {
+        ResponseStream {
+            body: StreamBody {
+                url: body.url().to_string(),
+                reader: Arc::new(tokio::sync::Mutex::new(body)),
+                method,
+                runtime,
+            },
+            _permit: permit,
+            replay,
+        }
+    }
+
+    pub fn body(&self) -> StreamBody {
+        self.body.clone()
+    }
+
+    /// The request of the stream, to send it again. None if its body can't be replayed.
+    pub fn replay(&self) -> Option<Replay> {
+        self.replay.as_ref()?.try_clone()
+    }
+}
+
+impl StreamBody {
+    /// Whether `self` and `other` read the same body.
+    pub fn is(&self, other: &StreamBody) -> bool {
+        Arc::ptr_eq(&self.reader, &other.reader)
+    }
+
+    /// Reads the next chunk of the body, releasing the GIL while waiting for it.
+    pub fn next_chunk(&self, py: Python) -> Result<Option<Bytes>> {
+        let reader = self.reader.clone();
+        let future = async move { reader.lock().await.chunk().await };
+        let result = py.allow_threads(|| self.runtime.block_on(future));
+        result.map_err(|err| self.error(py, err))
+    }
+
+    /// Reads the rest of the body, releasing the GIL while waiting for it.
+    pub fn read_to_end(&self, py: Python) -> Result<Vec<u8>> {
+        let reader = self.reader.clone();
+        let future = async move { reader.lock().await.read_to_end().await };
+        let result = py.allow_threads(|| self.runtime.block_on(future));
+        result.map_err(|err| self.error(py, err))
+    }
+
+    fn error(&self, py: Python, err: anyhow::Error) -> anyhow::Error {
+        // Reading the body doesn't connect, so whether the request went through a proxy
+        // doesn't matter
+        request_error(py, err, &self.method, &self.url, false).into()
+    }
+}
+
+/// The request of a streamed response, to send it again: event streams reconnect when the
+/// connection is lost.
+pub struct Replay {
+    // RequestBuilder is not Sync, only accessed to be cloned
+    request: Mutex<RequestBuilder>,
+    max_redirects: Option<usize>,
+    referer: bool,
+    decompress: bool,
+    limits: BodyLimits,
+    limiter: Option<Arc<HostLimiter>>,
+    method: String,
+    url: String,
+    runtime: ClientRuntime,
+    // Whether the request is sent through the proxy of the client
+    proxied: bool,
+}
+
+impl Replay {
+    pub fn new(
+        request: &RequestBuilder,
+        max_redirects: Option<usize>,
+        referer: bool,
+        decompress: bool,
+        limits: BodyLimits,
+        limiter: Option<Arc<HostLimiter>>,
+        method: &str,
+        url: &str,
+        runtime: &ClientRuntime,
+        proxied: bool,
+    ) -> Option<Self> {
+        Some(Replay {
+            request: Mutex::new(request.try_clone()?),
+            max_redirects,
+            referer,
+            decompress,
+            limits,
+            limiter,
+            method: method.to_string(),
+            url: url.to_string(),
+            runtime: runtime.clone(),
+            proxied,
+        })
+    }
+
+    fn try_clone(&self) -> Option<Self> {
+        let request = self.request.lock().unwrap().try_clone()?;
+        Some(Replay {
+            request: Mutex::new(request),
+            max_redirects: self.max_redirects,
+            referer: self.referer,
+            decompress: self.decompress,
+            limits: self.limits,
+            limiter: self.limiter.clone(),
+            method: self.method.clone(),
+            url: self.url.clone(),
+            runtime: self.runtime.clone(),
+            proxied: self.proxied,
+        })
+    }
+
+    /// Sends the request again after `delay`, with the extra `headers`, releasing the GIL.
+    /// Returns the status code and the headers of the response, and its unread body.
+    pub fn send(
+        &self,
+        py: Python,
+        delay: Duration,
+        headers: HeaderMap,
+    ) -> Result<(u16, HeaderMap, ResponseStream)> {
+        let request = self.request.lock().unwrap().try_clone();
+        let request = request
+            .ok_or_else(|| anyhow!("The request can't be sent again"))?
+            .headers(headers);
+        let (max_redirects, referer) = (self.max_redirects, self.referer);
+        let limiter = self.limiter.clone();
+        let future = async move {
+            tokio::time::sleep(delay).await;
+            send_following_redirects(
+                request,
+                max_redirects,
+                referer,
+                limiter,
+                |request_builder, limit| send_with_retry(request_builder, None, limit),
+            )
+            .await
+        };
+        let result = py.allow_threads(|| self.runtime.block_on(future));
+        let Sent {
+            mut resp, permit, ..
+        } = result.map_err(|err| -> anyhow::Error {
+            request_error(py, err, &self.method, &self.url, self.proxied).into()
+        })?;
+        let decoder = match self.decompress {
+            true => BodyDecoder::detect(resp.headers_mut(), self.limits.max_decompressed_size),
+            false => None,
+        };
+        let status_code = resp.status().as_u16();
+        let headers = resp.headers().clone();
+        let body = BodyReader::new(resp, decoder, self.limits.max_content_length).map_err(
+            |err| -> anyhow::Error {
+                request_error(py, err, &self.method, &self.url, self.proxied).into()
+            },
+        )?;
+        let stream = ResponseStream::new(
+            body,
+            self.method.clone(),
+            self.runtime.clone(),
+            permit,
+            self.try_clone(),
+        );
+        Ok((status_code, headers, stream))
+    }
+}
+
+/// Where an iterator takes body chunks from.
+enum ChunkSource {
+    /// The body is still streamed from the server.
+    Stream(Py<Response>),
+    /// The body has already been read into memory.
+    Buffered(Option<Bytes>),
+}
+
+/// Reads body chunks and optionally regroups them into chunks of `chunk_size` bytes.
+struct ChunkReader {
+    source: ChunkSource,
+    chunk_size: Option<usize>,
+    buf: BytesMut,
+    eof: bool,
+}
+
+impl ChunkReader {
+    fn new(response: &Bound<'_, Response>, chunk_size: Option<usize>) -> Result<Self> {
+        if chunk_size == Some(0) {
+            return Err(anyhow!("chunk_size must be greater than 0"));
+        }
+        let py = response.py();
+        let source = {
+            let resp = response.borrow();
+            if resp.is_streaming() {
+                ChunkSource::Stream(response.clone().unbind())
+            } else {
+                let content = resp.buffered_content(py)?;
+                ChunkSource::Buffered(Some(Bytes::copy_from_slice(content.as_bytes(py))))
+            }
+        };
+        Ok(ChunkReader {
+            source,
+            chunk_size,
+            buf: BytesMut::new(),
+            eof: false,
+        })
+    }
+
+    fn next_raw(&mut self, py: Python) -> Result<Option<Bytes>> {
+        match &mut self.source {
+            ChunkSource::Stream(response) => Response::next_stream_chunk(response.bind(py)),
+            ChunkSource::Buffered(content) => Ok(content.take()),
+        }
+    }
+
+    fn next(&mut self, py: Python) -> Result<Option<Bytes>> {
+        let Some(chunk_size) = self.chunk_size else {
+            // Skip empty chunks, an empty item must not look like the end of the body
+            while let Some(chunk) = self.next_raw(py)? {
+                if !chunk.is_empty() {
+                    return Ok(Some(chunk));
+                }
+            }
+            return Ok(None);
+        };
+        while !self.eof && self.buf.len() < chunk_size {
+            match self.next_raw(py)? {
+                Some(chunk) => self.buf.extend_from_slice(&chunk),
+                None => self.eof = true,
+            }
+        }
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+        let n = chunk_size.min(self.buf.len());
+        Ok(Some(self.buf.split_to(n).freeze()))
+    }
+}
+
+/// Decodes body chunks into text incrementally, so multi-byte characters may span chunks.
+struct TextReader {
+    reader: ChunkReader,
+    decoder: Option<Decoder>,
+}
+
+impl TextReader {
+    fn new(response: &Bound<'_, Response>, chunk_size: Option<usize>) -> Result<Self> {
+        let encoding = response.borrow_mut().stream_encoding(response.py())?;
+        let encoding = Encoding::for_label(encoding.as_bytes())
+            .ok_or_else(|| anyhow!("Unsupported charset: {}", encoding))?;
+        Self::with_encoding(response, chunk_size, encoding)
+    }
+
+    fn with_encoding(
+        response: &Bound<'_, Response>,
+        chunk_size: Option<usize>,
+        encoding: &'static Encoding,
+    ) -> Result<Self> {
+        Ok(TextReader {
+            reader: ChunkReader::new(response, chunk_size)?,
+            decoder: Some(encoding.new_decoder()),
+        })
+    }
+
+    fn next(&mut self, py: Python) -> Result<Option<String>> {
+        loop {
+            let Some(decoder) = self.decoder.as_mut() else {
+                return Ok(None);
+            };
+            let chunk = self.reader.next(py)?;
+            let last = chunk.is_none();
+            let src = chunk.as_deref().unwrap_or_default();
+            let capacity = decoder
+                .max_utf8_buffer_length(src.len())
+                .ok_or_else(|| anyhow!("Chunk is too large to decode"))?;
+            let mut text = String::with_capacity(capacity);
+            let _ = decoder.decode_to_string(src, &mut text, last);
+            if last {
+                self.decoder = None;
+            }
+            if !text.is_empty() {
+                return Ok(Some(text));
+            }
+        }
+    }
+}
+
+/// Iterator over the response body as bytes.
+#[pyclass]
+pub struct BytesIterator {
+    reader: ChunkReader,
+}
+
+impl BytesIterator {
+    pub fn new(response: &Bound<'_, Response>, chunk_size: Option<usize>) -> Result<Self> {
+        Ok(BytesIterator {
+            reader: ChunkReader::new(response, chunk_size)?,
+        })
+    }
+}
+
+#[pymethods]
+impl BytesIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> Result<Option<Py<PyBytes>>> {
+        let chunk = self.reader.next(py)?;
+        Ok(chunk.map(|chunk| PyBytes::new(py, &chunk).unbind()))
+    }
+}
+
+/// Iterator over the response body as decoded text.
+#[pyclass]
+pub struct TextIterator {
+    reader: TextReader,
+}
+
+impl TextIterator {
+    pub fn new(response: &Bound<'_, Response>, chunk_size: Option<usize>) -> Result<Self> {
+        Ok(TextIterator {
+            reader: TextReader::new(response, chunk_size)?,
+        })
+    }
+}
+
+#[pymethods]
+impl TextIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> Result<Option<String>> {
+        self.reader.next(py)
+    }
+}
+
+/// Splits decoded body text into lines, ending with `\n`, `\r\n` or `\r`.
+pub struct LineReader {
+    reader: TextReader,
+    // Text read but not returned yet, from `start`: the lines before are dropped once per chunk
+    pending: String,
+    start: usize,
+    eof: bool,
+}
+
+impl LineReader {
+    fn new(reader: TextReader) -> Self {
+        LineReader {
+            reader,
+            pending: String::new(),
+            start: 0,
+            eof: false,
+        }
+    }
+
+    /// Reads the body as UTF-8, whatever its declared charset, like event streams are.
+    pub fn utf8(response: &Bound<'_, Response>) -> Result<Self> {
+        let reader = TextReader::with_encoding(response, None, encoding_rs::UTF_8)?;
+        Ok(Self::new(reader))
+    }
+
+    /// Splits the first complete line off `pending`. A trailing `\r` is only a complete
+    /// line ending once more text has arrived, since it may be followed by `\n`.
+    fn split_line(&mut self) -> Option<String> {
+        let rest = &self.pending[self.start..];
+        let pos = rest.find(['\n', '\r'])?;
+        let ending_len = match &rest.as_bytes()[pos..] {
+            [b'\r', b'\n', ..] => 2,
+            [b'\r'] if !self.eof => return None,
+            _ => 1,
+        };
+        let line = rest[..pos].to_string();
+        self.start += pos + ending_len;
+        Some(line)
+    }
+
+    /// The next line, without its ending. The last line may be unterminated.
+    pub fn next_line(&mut self, py: Python) -> Result<Option<String>> {
+        loop {
+            if let Some(line) = self.split_line() {
+                return Ok(Some(line));
+            }
+            if self.eof {
+                if self.start == self.pending.len() {
+                    return Ok(None);
+                }
+                let line = self.pending[self.start..].to_string();
+                self.start = self.pending.len();
+                return Ok(Some(line));
+            }
+            match self.reader.next(py)? {
+                Some(text) => {
+                    self.pending.drain(..self.start);
+                    self.start = 0;
+                    self.pending.push_str(&text);
+                }
+                None => self.eof = true,
+            }
+        }
+    }
+}
+
+/// Iterator over the lines of the response body as decoded text, without line endings.
+#[pyclass]
+pub struct LinesIterator {
+    reader: LineReader,
+}
+
+impl LinesIterator {
+    pub fn new(response: &Bound<'_, Response>) -> Result<Self> {
+        Ok(LinesIterator {
+            reader: LineReader::new(TextReader::new(response, None)?),
+        })
+    }
+}
+
+#[pymethods]
+impl LinesIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> Result<Option<String>> {
+        self.reader.next_line(py)
+    }
+}
@@ -0,0 +1,196 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use pyo3::prelude::*;
+use rquest::dns::{Addrs, Name, Resolve, Resolving};
+use tokio::time::Instant;
+use tower::{Layer, Service};
+
+tokio::task_local! {
+    // Connection timings of the request driven by the current task
+    static CONNECTION_TIMINGS: Arc<Mutex<ConnectionTimings>>;
+}
+
+/// Durations of the connection setup, set only if the request opened a new connection.
+///
+/// When several connections are opened, as for redirects to other hosts, the last one wins.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConnectionTimings {
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+}
+
+/// Runs `future`, collecting the timings of the connections it opens.
+pub async fn with_connection_timings<F: Future>(future: F) -> (F::Output, ConnectionTimings) {
+    let timings = Arc::new(Mutex::new(ConnectionTimings::default()));
+    let output = CONNECTION_TIMINGS.scope(Arc::clone(&timings), future).await;
+    let timings = *timings.lock().unwrap();
+    (output, timings)
+}
+
+fn current_timings() -> Option<Arc<Mutex<ConnectionTimings>>> {
+    CONNECTION_TIMINGS.try_with(Arc::clone).ok()
+}
+
+/// Resolves names with `getaddrinfo` on the blocking pool. This is what the default resolver of
+/// `rquest` does, which is not exported to be wrapped.
+pub struct GaiResolver;
+
+impl Resolve for GaiResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host, 0)).await?;
+            Ok(Box::new(addrs) as Addrs)
+        })
+    }
+}
+
+/// Resolver recording the duration of the lookups of `inner`, and otherwise passing them through.
+///
+/// The client applies its resolve overrides before, so overridden names don't reach it.
+pub struct TimingResolver<R> {
+    inner: R,
+}
+
+impl<R> TimingResolver<R> {
+    pub fn new(inner: R) -> Self {
+        TimingResolver { inner }
+    }
+}
+
+impl<R: Resolve> Resolve for TimingResolver<R> {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolving = self.inner.resolve(name);
+        let Some(timings) = current_timings() else {
+            return resolving;
+        };
+        Box::pin(async move {
+            let start = Instant::now();
+            let addrs = resolving.await?;
+            timings.lock().unwrap().dns = Some(start.elapsed());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Connector layer recording the duration of the TCP connect and TLS handshake.
+///
+/// The connector resolves the name too, so the DNS duration is subtracted. The TCP connect and
+/// the TLS handshake happen in a single call of the `rquest` connector, without a hook between
+/// them, so they are measured together.
+#[derive(Clone)]
+pub struct TimingLayer;
+
+impl<S> Layer<S> for TimingLayer {
+    type Service = TimingConnector<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimingConnector { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct TimingConnector<S> {
+    inner: S,
+}
+
+impl<S, R> Service<R> for TimingConnector<S>
+where
+    S: Service<R>,
+    S::Response: Send + 'static,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let timings = current_timings();
+        // A redirect or a retry may open another connection in the same scope, so forget the
+        // previous one to not mix its DNS duration with the new connect
+        if let Some(timings) = &timings {
+            *timings.lock().unwrap() = ConnectionTimings::default();
+        }
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let start = Instant::now();
+            let conn = future.await?;
+            if let Some(timings) = timings {
+                let mut timings = timings.lock().unwrap();
+                let dns = timings.dns.unwrap_or_default();
+                timings.connect = Some(start.elapsed().saturating_sub(dns));
+            }
+            Ok(conn)
+        })
+    }
+}
+
+/// Timing breakdown of a request, in seconds.
+///
+/// Measured inside the Tokio runtime, so the time spent waiting for the GIL or for a
+/// rate limit slot is not included. Retries are included in `ttfb`.
+#[pyclass(frozen)]
+#[derive(Clone, Debug)]
+pub struct Timings {
+    /// DNS resolution. None if an existing connection was reused or no lookup was needed.
+    #[pyo3(get)]
+    pub dns: Option<f64>,
+    /// TCP connect and TLS handshake together, as `rquest` doesn't tell them apart. None if an
+    /// existing connection was reused.
+    #[pyo3(get)]
+    pub connect: Option<f64>,
+    /// TLS handshake. Always None: it is not measured separately, see `connect`.
+    #[pyo3(get)]
+    pub tls: Option<f64>,
+    /// From sending the request to receiving the response headers, connection setup included.
+    #[pyo3(get)]
+    pub ttfb: f64,
+    /// Reading the response body. None for streamed responses.
+    #[pyo3(get)]
+    pub download: Option<f64>,
+    /// Total duration of the request: `ttfb` plus `download`.
+    #[pyo3(get)]
+    pub total: f64,
+}
+
+impl Timings {
+    pub fn new(connection: ConnectionTimings, ttfb: Duration, download: Option<Duration>) -> Self {
+        let total = ttfb + download.unwrap_or_default();
+        Timings {
+            dns: connection.dns.map(|d| d.as_secs_f64()),
+            connect: connection.connect.map(|d| d.as_secs_f64()),
+            tls: None,
+            ttfb: ttfb.as_secs_f64(),
+            download: download.map(|d| d.as_secs_f64()),
+            total: total.as_secs_f64(),
+        }
+    }
+}
+
+#[pymethods]
+impl Timings {
+    fn __repr__(&self) -> String {
+        format!(
+            "Timings(dns={}, connect={}, tls={}, ttfb={}, download={}, total={})",
+            repr_seconds(self.dns),
+            repr_seconds(self.connect),
+            repr_seconds(self.tls),
+            self.ttfb,
+            repr_seconds(self.download),
+            self.total
+        )
+    }
+}
+
+fn repr_seconds(seconds: Option<f64>) -> String {
+    seconds.map_or_else(|| "None".to_string(), |s| s.to_string())
+}
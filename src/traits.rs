@@ -43,10 +43,7 @@ impl HeadersTraits for HeaderMap {
         for (key, value) in self {
             index_map.insert(
                 key.as_str().to_string(),
-                value
-                    .to_str()
-                    .unwrap_or_else(|v| panic!("Invalid header value: {v:?}"))
-                    .to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
             );
         }
         index_map
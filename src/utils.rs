@@ -1,10 +1,12 @@
 use std::cmp::min;
 use std::sync::LazyLock;
+use std::time::{Duration, SystemTime};
 
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, X_USER_DEFINED};
 use foldhash::fast::RandomState;
 use indexmap::IndexMap;
-use rquest::{X509Store, X509StoreBuilder, X509};
-use tracing;
+use rquest::{Version, X509Store, X509StoreBuilder, X509};
 
 /// Loads the CA certificates from venv var PRIMP_CA_BUNDLE or the WebPKI certificate store
 pub fn load_ca_certs() -> Option<&'static X509Store> {
@@ -66,29 +68,295 @@ pub fn get_encoding_from_headers(
         })
 }
 
-/// Get encoding from the `<meta charset="...">` tag within the first 2048 bytes of HTML content.
+/// Get encoding from the `<meta>` tags within the first 1024 bytes of HTML content.
+///
+/// Implements the "prescan a byte stream to determine its encoding" algorithm of the HTML
+/// standard: comments, and the text and attributes of other tags are skipped, so that a
+/// `charset=` in a script doesn't match. Returns the first valid label, as written lowercase,
+/// or the first invalid one if there is no valid one.
 pub fn get_encoding_from_content(raw_bytes: &[u8]) -> Option<String> {
-    let start_sequence: &[u8] = b"charset=";
-    let max_index = min(2048, raw_bytes.len());
-
-    if let Some(start_index) = raw_bytes[..max_index]
-        .windows(start_sequence.len())
-        .position(|window| window == start_sequence)
-    {
-        let remaining_bytes = &raw_bytes[start_index + start_sequence.len()..max_index];
-        if let Some(end_index) = remaining_bytes
-            .iter()
-            .enumerate()
-            .position(|(i, &byte)| matches!(byte, b' ' | b'"' | b'>') && i > 0)
+    let bytes = &raw_bytes[..min(1024, raw_bytes.len())];
+    let mut invalid = None;
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+        if rest.starts_with(b"<!--") {
+            // The end of the comment may overlap its start, as in `<!-->`
+            let Some(end) = find(&bytes[pos + 2..], b"-->") else {
+                break;
+            };
+            pos += 2 + end + 3;
+            continue;
+        }
+        if rest.len() > 5
+            && rest[..5].eq_ignore_ascii_case(b"<meta")
+            && (is_whitespace(rest[5]) || rest[5] == b'/')
+        {
+            pos += 5;
+            if let Some(label) = prescan_meta(bytes, &mut pos) {
+                let label = label.trim_matches('"').to_string();
+                match Encoding::for_label(label.as_bytes()) {
+                    Some(_) => return Some(label),
+                    None => invalid = invalid.or(Some(label)),
+                }
+            }
+        } else if rest.len() > 2
+            && rest[0] == b'<'
+            && (rest[1].is_ascii_alphabetic() || (rest[1] == b'/' && rest[2].is_ascii_alphabetic()))
         {
-            let charset_slice = &remaining_bytes[..end_index];
-            let charset = String::from_utf8_lossy(charset_slice)
-                .trim_matches('"')
-                .to_ascii_lowercase();
-            return Some(charset);
+            // Skip the tag name and the attributes of another tag
+            while pos < bytes.len() && !is_whitespace(bytes[pos]) && bytes[pos] != b'>' {
+                pos += 1;
+            }
+            while get_attribute(bytes, &mut pos).is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            let Some(end) = find(rest, b">") else {
+                break;
+            };
+            pos += end;
+        }
+        pos += 1;
+    }
+    invalid
+}
+
+/// The charset declared by the attributes of a `<meta>` tag, with `charset`, or with
+/// `http-equiv="content-type"` and `content`.
+fn prescan_meta(bytes: &[u8], pos: &mut usize) -> Option<String> {
+    let mut names = Vec::new();
+    let mut got_pragma = false;
+    let mut need_pragma = None;
+    let mut charset = None;
+    while let Some((name, value)) = get_attribute(bytes, pos) {
+        if names.contains(&name) {
+            continue;
+        }
+        match name.as_str() {
+            "http-equiv" => got_pragma |= value == "content-type",
+            "content" if charset.is_none() => {
+                if let Some(label) = charset_from_content(&value) {
+                    charset = Some(label);
+                    need_pragma = Some(true);
+                }
+            }
+            "charset" => {
+                charset = Some(value.clone());
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+        names.push(name);
+    }
+    match need_pragma? {
+        true if !got_pragma => None,
+        _ => charset,
+    }
+}
+
+/// The "get an attribute" algorithm of the prescan. Names and values are lowercase.
+fn get_attribute(bytes: &[u8], pos: &mut usize) -> Option<(String, String)> {
+    let at = |pos: usize| bytes.get(pos).copied();
+    while at(*pos).is_some_and(|b| is_whitespace(b) || b == b'/') {
+        *pos += 1;
+    }
+    if at(*pos)? == b'>' {
+        return None;
+    }
+    let mut name = Vec::new();
+    let mut value = Vec::new();
+    loop {
+        match at(*pos)? {
+            b'=' if !name.is_empty() => {
+                *pos += 1;
+                break;
+            }
+            b if is_whitespace(b) => {
+                while at(*pos).is_some_and(is_whitespace) {
+                    *pos += 1;
+                }
+                if at(*pos)? != b'=' {
+                    return Some(attribute(name, value));
+                }
+                *pos += 1;
+                break;
+            }
+            b'/' | b'>' => return Some(attribute(name, value)),
+            b => name.push(b.to_ascii_lowercase()),
         }
+        *pos += 1;
+    }
+    while at(*pos).is_some_and(is_whitespace) {
+        *pos += 1;
+    }
+    match at(*pos)? {
+        quote @ (b'"' | b'\'') => {
+            *pos += 1;
+            loop {
+                match at(*pos)? {
+                    b if b == quote => {
+                        *pos += 1;
+                        return Some(attribute(name, value));
+                    }
+                    b => value.push(b.to_ascii_lowercase()),
+                }
+                *pos += 1;
+            }
+        }
+        b'>' => Some(attribute(name, value)),
+        _ => loop {
+            match at(*pos)? {
+                b if is_whitespace(b) || b == b'>' => return Some(attribute(name, value)),
+                b => value.push(b.to_ascii_lowercase()),
+            }
+            *pos += 1;
+        },
+    }
+}
+
+fn attribute(name: Vec<u8>, value: Vec<u8>) -> (String, String) {
+    (
+        String::from_utf8_lossy(&name).into_owned(),
+        String::from_utf8_lossy(&value).into_owned(),
+    )
+}
+
+/// The charset of a `content` attribute like `text/html; charset=utf-8`.
+fn charset_from_content(content: &str) -> Option<String> {
+    let bytes = content.as_bytes();
+    let mut pos = 0;
+    loop {
+        pos += find(&bytes[pos..], b"charset")? + 7;
+        while bytes.get(pos).copied().is_some_and(is_whitespace) {
+            pos += 1;
+        }
+        if bytes.get(pos) == Some(&b'=') {
+            pos += 1;
+            break;
+        }
+    }
+    while bytes.get(pos).copied().is_some_and(is_whitespace) {
+        pos += 1;
+    }
+    let rest = &content[pos..];
+    match rest.as_bytes().first()? {
+        quote @ (b'"' | b'\'') => {
+            let end = rest[1..].find(*quote as char)?;
+            Some(rest[1..1 + end].to_string())
+        }
+        _ => {
+            let end = rest
+                .find(|c: char| c.is_ascii_whitespace() || c == ';')
+                .unwrap_or(rest.len());
+            Some(rest[..end].to_string()).filter(|label| !label.is_empty())
+        }
+    }
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Get encoding from the byte order mark at the start of the content.
+pub fn get_encoding_from_bom(raw_bytes: &[u8]) -> Option<&'static Encoding> {
+    Encoding::for_bom(raw_bytes).map(|(encoding, _)| encoding)
+}
+
+/// Guesses the encoding of the content from its bytes, with `chardetng`, like Firefox does
+/// for unlabeled pages. The top-level domain of `url` helps to choose between legacy encodings.
+/// ASCII content is reported as UTF-8.
+pub fn guess_encoding(raw_bytes: &[u8], url: &str) -> &'static Encoding {
+    let mut detector = EncodingDetector::new();
+    if !detector.feed(raw_bytes, true) {
+        return UTF_8;
+    }
+    let tld = url
+        .split_once("://")
+        .and_then(|(_, rest)| rest.split(['/', '?', '#', ':']).next())
+        .and_then(|host| host.trim_end_matches('.').rsplit('.').next())
+        .filter(|tld| !tld.is_empty() && tld.bytes().all(|b| b.is_ascii_alphabetic()))
+        .map(str::to_ascii_lowercase);
+    detector.guess(tld.as_deref().map(str::as_bytes), true)
+}
+
+/// Determines the encoding of a response body the way browsers do, following the encoding
+/// sniffing algorithm of the HTML standard: the byte order mark, the charset of the
+/// `Content-Type` header, the `<meta>` prescan, then a guess from the content.
+pub fn sniff_encoding(
+    headers: &IndexMap<String, String, RandomState>,
+    raw_bytes: &[u8],
+    url: &str,
+) -> String {
+    if let Some(encoding) = get_encoding_from_bom(raw_bytes) {
+        return encoding.name().to_ascii_lowercase();
+    }
+    let declared = get_encoding_from_headers(headers)
+        .filter(|label| Encoding::for_label(label.as_bytes()).is_some())
+        .or_else(|| {
+            // A `<meta>` can't declare UTF-16, the document would not be ASCII-compatible
+            let label = get_encoding_from_content(raw_bytes)?;
+            match Encoding::for_label(label.as_bytes())? {
+                encoding if encoding == UTF_16BE || encoding == UTF_16LE => {
+                    Some("utf-8".to_string())
+                }
+                encoding if encoding == X_USER_DEFINED => Some("windows-1252".to_string()),
+                _ => Some(label),
+            }
+        });
+    declared.unwrap_or_else(|| guess_encoding(raw_bytes, url).name().to_ascii_lowercase())
+}
+
+/// Parsed `Content-Range` header value.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ContentRange {
+    /// First and last byte positions, None for `*`.
+    pub range: Option<(u64, u64)>,
+    /// Complete length of the body, None for `*`.
+    pub total: Option<u64>,
+}
+
+/// Parses a `Content-Range` header value: `bytes <start>-<end>/<total>` or `bytes */<total>`.
+pub fn parse_content_range(value: &str) -> Option<ContentRange> {
+    let (range, total) = value.strip_prefix("bytes ")?.trim().split_once('/')?;
+    let total = match total {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    let range = match range {
+        "*" => None,
+        range => {
+            let (start, end) = range.split_once('-')?;
+            Some((start.parse().ok()?, end.parse().ok()?))
+        }
+    };
+    Some(ContentRange { range, total })
+}
+
+/// Parses a `Retry-After` header value: a number of seconds or an HTTP date, relative to `now`.
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+/// Name of an HTTP version, as in the status line: "HTTP/1.1", "HTTP/2"...
+pub fn http_version_name(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_11 => "HTTP/1.1",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "unknown",
     }
-    None
 }
 
 #[cfg(test)]
@@ -211,4 +479,124 @@ mod utils_tests {
         let raw_html = b"<html><head></head></html>";
         assert_eq!(get_encoding_from_content(raw_html), None);
     }
+
+    #[test]
+    fn test_get_encoding_from_content_prescan() {
+        let cases: &[(&[u8], Option<&str>)] = &[
+            (
+                b"<meta http-equiv=Content-Type content='text/html; charset=Shift_JIS'>",
+                Some("shift_jis"),
+            ),
+            (
+                b"<META CONTENT=\"text/html;charset = 'koi8-r'\" HTTP-EQUIV=\"content-type\">",
+                Some("koi8-r"),
+            ),
+            // content without http-equiv is not a declaration
+            (b"<meta content=\"text/html; charset=koi8-r\">", None),
+            (b"<meta charset=\"bogus\"><meta charset=gbk>", Some("gbk")),
+            (
+                b"<!-- <meta charset=big5> --><meta charset=euc-kr>",
+                Some("euc-kr"),
+            ),
+            (b"<script>var s = 'charset=big5';</script>", None),
+            (
+                b"<a title='<meta charset=big5>'><meta charset='utf-8'/>",
+                Some("utf-8"),
+            ),
+        ];
+        for (html, charset) in cases {
+            assert_eq!(
+                get_encoding_from_content(html).as_deref(),
+                *charset,
+                "{}",
+                String::from_utf8_lossy(html)
+            );
+        }
+        let mut late = vec![b' '; 1024];
+        late.extend_from_slice(b"<meta charset=big5>");
+        assert_eq!(get_encoding_from_content(&late), None);
+    }
+
+    #[test]
+    fn test_sniff_encoding() {
+        let mut headers = IndexMap::default();
+        let url = "https://example.com/";
+        assert_eq!(sniff_encoding(&headers, b"\xef\xbb\xbfabc", url), "utf-8");
+        assert_eq!(sniff_encoding(&headers, b"\xff\xfea\x00", url), "utf-16le");
+        assert_eq!(
+            sniff_encoding(&headers, b"<meta charset=utf-16>", url),
+            "utf-8"
+        );
+        assert_eq!(
+            sniff_encoding(&headers, b"<meta charset=windows-1251>", url),
+            "windows-1251"
+        );
+        assert_eq!(sniff_encoding(&headers, b"plain ascii", url), "utf-8");
+        assert_eq!(
+            sniff_encoding(&headers, "d\u{e9}j\u{e0} vu".as_bytes(), url),
+            "utf-8"
+        );
+        headers.insert(
+            String::from("Content-Type"),
+            String::from("text/html; charset=ISO-8859-2"),
+        );
+        assert_eq!(
+            sniff_encoding(&headers, b"<meta charset=windows-1251>", url),
+            "iso-8859-2"
+        );
+        // The byte order mark wins over the header
+        assert_eq!(sniff_encoding(&headers, b"\xef\xbb\xbfabc", url), "utf-8");
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(
+            parse_content_range("bytes 100-199/1000"),
+            Some(ContentRange {
+                range: Some((100, 199)),
+                total: Some(1000)
+            })
+        );
+        assert_eq!(
+            parse_content_range("bytes 0-9/*"),
+            Some(ContentRange {
+                range: Some((0, 9)),
+                total: None
+            })
+        );
+        assert_eq!(
+            parse_content_range("bytes */1000"),
+            Some(ContentRange {
+                range: None,
+                total: Some(1000)
+            })
+        );
+        assert_eq!(parse_content_range("items 0-9/10"), None);
+        assert_eq!(parse_content_range("bytes 0-x/10"), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = httpdate::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        // A date in the past means no delay
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_http_version_name() {
+        assert_eq!(http_version_name(Version::HTTP_11), "HTTP/1.1");
+        assert_eq!(http_version_name(Version::HTTP_2), "HTTP/2");
+    }
 }
@@ -0,0 +1,521 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+use sxd_document::{dom, Package};
+use sxd_xpath::nodeset::Node as SxdNode;
+use sxd_xpath::{Context, Factory, Value};
+use yoke::{Yoke, Yokeable};
+
+use crate::html::{Document, NodeData};
+
+/// A node of the XPath data model: a node of the document, or an attribute of an element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XNode {
+    Node(usize),
+    /// The element, and the index of the attribute.
+    Attr(usize, usize),
+}
+
+impl XNode {
+    // Sort key in document order: the attributes of an element come before its children
+    fn key(self) -> (usize, usize) {
+        match self {
+            XNode::Node(id) => (id, 0),
+            XNode::Attr(id, index) => (id, index + 1),
+        }
+    }
+}
+
+/// Result of an XPath expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum XValue {
+    /// Nodes in document order, without duplicates.
+    Nodes(Vec<XNode>),
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+/// A compiled XPath 1.0 expression, parsed and evaluated by `sxd-xpath`.
+///
+/// The full expression language is supported, except variables and the `id()` and `lang()`
+/// functions. Element and attribute names are matched as they are in the document, which is
+/// lowercase for HTML.
+///
+/// Where `sxd-xpath` departs from the specification: `<`, `<=`, `>` and `>=` compare the first
+/// node of a node-set only, `!=` is the negation of `=`, a fractional number in a predicate
+/// selects a position, `number()` accepts exponents, and the `following` axis of an attribute
+/// leaves out the descendants of its element.
+#[derive(Debug)]
+pub struct XPath(sxd_xpath::XPath);
+
+impl XPath {
+    pub fn parse(expr: &str) -> Result<Self> {
+        match Factory::new().build(expr) {
+            Ok(Some(xpath)) => Ok(XPath(xpath)),
+            Ok(None) => Err(anyhow!("Invalid XPath {:?}: empty expression", expr)),
+            Err(err) => Err(anyhow!("Invalid XPath {:?}: {}", expr, err)),
+        }
+    }
+
+    /// Evaluates the expression with the node `context`.
+    pub fn evaluate(&self, doc: &Document, context: usize) -> Result<XValue> {
+        let sxd = doc.sxd_copy();
+        let copy = sxd.0.get();
+        let value = self
+            .0
+            .evaluate(&Context::new(), copy.nodes[context])
+            .map_err(|err| anyhow!("XPath evaluation failed: {}", err))?;
+        Ok(match value {
+            Value::Nodeset(nodeset) => {
+                let mut nodes: Vec<XNode> = nodeset
+                    .iter()
+                    .filter_map(|node| copy.ids.get(&node).copied())
+                    .collect();
+                nodes.sort_unstable_by_key(|node| node.key());
+                XValue::Nodes(nodes)
+            }
+            Value::String(s) => XValue::String(s),
+            Value::Number(n) => XValue::Number(n),
+            Value::Boolean(b) => XValue::Boolean(b),
+        })
+    }
+}
+
+/// Copy of a document in an `sxd-document`, built once and reused by the XPath queries.
+pub struct SxdCopy(Yoke<Copy<'static>, Box<Package>>);
+
+// SAFETY: the nodes of the copy point into its package only, which the copy owns. The package
+// has no reference counts or thread-local state, so the whole can move to another thread. It is
+// not `Sync` though, so the document keeps it behind a `Mutex`.
+unsafe impl Send for SxdCopy {}
+
+impl SxdCopy {
+    pub fn new(doc: &Document) -> Self {
+        SxdCopy(Yoke::attach_to_cart(Box::new(Package::new()), |package| {
+            Copy::new(doc, package.as_document())
+        }))
+    }
+}
+
+impl fmt::Debug for SxdCopy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SxdCopy").finish_non_exhaustive()
+    }
+}
+
+/// Copy of a document in an `sxd-document`, and the mapping between their nodes.
+#[derive(Yokeable)]
+struct Copy<'d> {
+    /// The copies of the nodes of the document, by id.
+    nodes: Vec<SxdNode<'d>>,
+    /// The nodes of the document, by copy.
+    ids: HashMap<SxdNode<'d>, XNode>,
+}
+
+impl<'d> Copy<'d> {
+    fn new(doc: &Document, sxd: dom::Document<'d>) -> Self {
+        let len = doc.descendants(Document::ROOT).end;
+        let mut nodes: Vec<SxdNode> = Vec::with_capacity(len);
+        // The nodes are hashed by address
+        #[allow(clippy::mutable_key_type)]
+        let mut ids = HashMap::with_capacity(len);
+        // Parents come before their children, so they are copied first
+        for id in 0..len {
+            let node = match &doc.node(id).data {
+                NodeData::Document => SxdNode::Root(sxd.root()),
+                NodeData::Element { name, attrs } => {
+                    let element = sxd.create_element(name.as_str());
+                    for (index, (name, value)) in attrs.iter().enumerate() {
+                        let attr = element.set_attribute_value(name.as_str(), value);
+                        ids.insert(SxdNode::Attribute(attr), XNode::Attr(id, index));
+                    }
+                    SxdNode::Element(element)
+                }
+                NodeData::Text(text) => SxdNode::Text(sxd.create_text(text)),
+                NodeData::Comment(text) => SxdNode::Comment(sxd.create_comment(text)),
+            };
+            match (doc.node(id).parent.map(|parent| nodes[parent]), node) {
+                (Some(SxdNode::Root(root)), SxdNode::Element(element)) => {
+                    root.append_child(element)
+                }
+                (Some(SxdNode::Root(root)), SxdNode::Comment(comment)) => {
+                    root.append_child(comment)
+                }
+                (Some(SxdNode::Element(parent)), SxdNode::Element(element)) => {
+                    parent.append_child(element)
+                }
+                (Some(SxdNode::Element(parent)), SxdNode::Text(text)) => parent.append_child(text),
+                (Some(SxdNode::Element(parent)), SxdNode::Comment(comment)) => {
+                    parent.append_child(comment)
+                }
+                // The document node, and the text of the document node, which the parser never
+                // produces
+                _ => {}
+            }
+            nodes.push(node);
+            ids.insert(node, XNode::Node(id));
+        }
+        Copy { nodes, ids }
+    }
+}
+
+#[cfg(test)]
+mod xpath_tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    const HTML: &str = r#"<div id="main"><a href="/1">one</a><a href="/2" class="x">two</a>
+<p>  some   text </p></div>"#;
+
+    fn eval(expr: &str) -> XValue {
+        let doc = Document::parse(HTML);
+        XPath::parse(expr)
+            .unwrap()
+            .evaluate(&doc, Document::ROOT)
+            .unwrap()
+    }
+
+    fn strings(expr: &str) -> Vec<String> {
+        let doc = Document::parse(HTML);
+        let XValue::Nodes(nodes) = XPath::parse(expr)
+            .unwrap()
+            .evaluate(&doc, Document::ROOT)
+            .unwrap()
+        else {
+            panic!("not a node-set: {}", expr);
+        };
+        nodes
+            .into_iter()
+            .map(|node| match node {
+                XNode::Node(id) => doc.text(id),
+                XNode::Attr(id, index) => doc.attrs(id)[index].1.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_location_paths() {
+        assert_eq!(strings("//a"), vec!["one", "two"]);
+        assert_eq!(strings("//a/@href"), vec!["/1", "/2"]);
+        assert_eq!(strings("//a[@class='x']/text()"), vec!["two"]);
+        assert_eq!(strings("//a[last()]/preceding-sibling::*"), vec!["one"]);
+        assert_eq!(
+            strings("//div[@id='main']/*[position() > 1]"),
+            vec!["two", "  some   text "]
+        );
+        assert_eq!(strings("//p/ancestor::div/@id"), vec!["main"]);
+        assert_eq!(strings("//a | //p"), vec!["one", "two", "  some   text "]);
+    }
+
+    #[test]
+    fn test_functions_and_operators() {
+        assert_eq!(eval("count(//a)"), XValue::Number(2.0));
+        assert_eq!(
+            eval("normalize-space(//p)"),
+            XValue::String("some text".into())
+        );
+        assert_eq!(
+            eval("concat(name(//a), '-', //a[2])"),
+            XValue::String("a-two".into())
+        );
+        assert_eq!(
+            eval("substring('12345', 2, 3)"),
+            XValue::String("234".into())
+        );
+        assert_eq!(eval("//a/@href = '/2'"), XValue::Boolean(true));
+        assert_eq!(eval("not(//table)"), XValue::Boolean(true));
+        assert_eq!(eval("1 + 2 * 3 div 4 mod 2"), XValue::Number(2.5));
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        for expr in ["", "//a[", "foo(", "//a/@", "1 +"] {
+            assert!(XPath::parse(expr).is_err(), "{:?}", expr);
+        }
+    }
+
+    const PAGE: &str = concat!(
+        r#"<div id="main"><h1 id="t">T</h1><!--note--><p class="a">P1</p>"#,
+        r#"<p class="b" data-n="2">P2<b>B</b></p><ul><li>1</li><li>2</li><li>3</li></ul></div>"#,
+        r#"<footer>F</footer>"#,
+    );
+
+    fn evaluate(expr: &str) -> (Document, Result<XValue>) {
+        let doc = Document::parse(PAGE);
+        let value = XPath::parse(expr).unwrap().evaluate(&doc, Document::ROOT);
+        (doc, value)
+    }
+
+    /// The selected nodes: element names, `@` and the name for attributes, and the text of
+    /// the other nodes.
+    fn labels(expr: &str) -> Vec<String> {
+        let (doc, value) = evaluate(expr);
+        let Ok(XValue::Nodes(nodes)) = value else {
+            panic!("not a node-set: {}", expr);
+        };
+        nodes
+            .into_iter()
+            .map(|node| match node {
+                XNode::Node(id) if doc.is_element(id) => doc.name(id).unwrap().to_string(),
+                XNode::Node(id) => doc.text(id),
+                XNode::Attr(id, index) => format!("@{}", doc.attrs(id)[index].0),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_axes() {
+        let cases: &[(&str, &[&str])] = &[
+            ("//div/child::*", &["h1", "p", "p", "ul"]),
+            ("//div/*", &["h1", "p", "p", "ul"]),
+            ("//div/node()", &["h1", "note", "p", "p", "ul"]),
+            ("//div/comment()", &["note"]),
+            ("//p/text()", &["P1", "P2"]),
+            ("//p//text()", &["P1", "P2", "B"]),
+            ("//ul/descendant::*", &["li", "li", "li"]),
+            ("//ul/descendant-or-self::*", &["ul", "li", "li", "li"]),
+            ("//li[2]/parent::*", &["ul"]),
+            ("//li[2]/..", &["ul"]),
+            ("//li/..", &["ul"]),
+            ("//li[2]/self::li", &["li"]),
+            ("//li[2]/self::p", &[]),
+            ("//li[2]/.", &["li"]),
+            ("//b/ancestor::*", &["html", "body", "div", "p"]),
+            (
+                "//b/ancestor-or-self::*",
+                &["html", "body", "div", "p", "b"],
+            ),
+            ("//b/ancestor::*[1]", &["p"]),
+            ("//b/ancestor::*[last()]", &["html"]),
+            ("//p[1]/following-sibling::*", &["p", "ul"]),
+            ("//ul/preceding-sibling::p", &["p", "p"]),
+            ("//ul/preceding-sibling::*[1]", &["p"]),
+            (
+                "//h1/following::*",
+                &["p", "p", "b", "ul", "li", "li", "li", "footer"],
+            ),
+            ("//li[1]/preceding::p", &["p", "p"]),
+            ("//li[1]/preceding::*[1]", &["b"]),
+            ("//p[@class='b']/@*", &["@class", "@data-n"]),
+            ("//p/attribute::class", &["@class", "@class"]),
+            ("//@data-n/..", &["p"]),
+            ("//@data-n/ancestor::div", &["div"]),
+            ("//@data-n/self::node()", &["@data-n"]),
+            ("//@data-n/child::node()", &[]),
+            ("/*", &["html"]),
+            ("/descendant::footer", &["footer"]),
+        ];
+        for &(expr, expected) in cases {
+            assert_eq!(labels(expr), expected, "{:?}", expr);
+        }
+    }
+
+    #[test]
+    fn test_predicates() {
+        let cases: &[(&str, &[&str])] = &[
+            ("//li[2]", &["2"]),
+            ("//li[last()]", &["3"]),
+            ("//li[position() < 3]", &["1", "2"]),
+            ("//li[. = '2' or . = '3']", &["2", "3"]),
+            ("//li[. > 1][1]", &["2"]),
+            ("(//li)[2]", &["2"]),
+            ("(//li)[position() = last() - 1]", &["2"]),
+            ("(//p | //li)[last()]", &["3"]),
+            ("(//p)[2]/b", &["B"]),
+            ("//p[b]", &["P2B"]),
+            ("//p[not(b)]", &["P1"]),
+            ("//p[@data-n]", &["P2B"]),
+            ("//p[@data-n = 2]", &["P2B"]),
+            ("//*[@id='t']", &["T"]),
+            ("//p[contains(@class, 'b')]/b", &["B"]),
+            ("//div[count(p) = 2]/h1", &["T"]),
+            ("//p[1]/following-sibling::*[2]", &["123"]),
+            ("//*[starts-with(name(), 'foot')]", &["F"]),
+        ];
+        for &(expr, expected) in cases {
+            let (doc, value) = evaluate(expr);
+            let Ok(XValue::Nodes(nodes)) = value else {
+                panic!("not a node-set: {}", expr);
+            };
+            let texts: Vec<String> = nodes
+                .into_iter()
+                .map(|node| match node {
+                    XNode::Node(id) => doc.text(id),
+                    XNode::Attr(..) => unreachable!(),
+                })
+                .collect();
+            assert_eq!(texts, expected, "{:?}", expr);
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        let string = |s: &str| XValue::String(s.to_string());
+        let cases = [
+            ("string-length('abc')", XValue::Number(3.0)),
+            ("starts-with('hello', 'he')", XValue::Boolean(true)),
+            ("contains('hello', 'z')", XValue::Boolean(false)),
+            ("substring-before('2024-01-02', '-')", string("2024")),
+            ("substring-after('2024-01-02', '-')", string("01-02")),
+            ("substring-after('abc', 'z')", string("")),
+            ("substring('12345', 1.5, 2.6)", string("234")),
+            ("substring('12345', 0, 3)", string("12")),
+            ("substring('12345', 2)", string("2345")),
+            ("translate('bar', 'abc', 'ABC')", string("BAr")),
+            ("translate('--aaa--', 'abc-', 'ABC')", string("AAA")),
+            ("normalize-space('  a \n b ')", string("a b")),
+            ("concat('a', 1, true())", string("a1true")),
+            ("string(1 div 0)", string("Infinity")),
+            ("string(-1 div 0)", string("-Infinity")),
+            ("string(0 div 0)", string("NaN")),
+            ("string(2.5)", string("2.5")),
+            ("number(' 12 ')", XValue::Number(12.0)),
+            ("number('-3.5')", XValue::Number(-3.5)),
+            ("boolean('')", XValue::Boolean(false)),
+            ("boolean('0')", XValue::Boolean(true)),
+            ("boolean(0)", XValue::Boolean(false)),
+            ("true() and not(false())", XValue::Boolean(true)),
+            ("floor(-1.5)", XValue::Number(-2.0)),
+            ("ceiling(1.2)", XValue::Number(2.0)),
+            ("round(2.5)", XValue::Number(3.0)),
+            ("round(-2.5)", XValue::Number(-2.0)),
+            ("sum(//li)", XValue::Number(6.0)),
+            ("count(//li | //p)", XValue::Number(5.0)),
+            ("count(/)", XValue::Number(1.0)),
+            ("name(/*)", string("html")),
+            ("name(//p/@data-n)", string("data-n")),
+            ("local-name(//h1)", string("h1")),
+            ("name(//nothing)", string("")),
+            ("string(//p[2]/@data-n)", string("2")),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(evaluate(expr).1.unwrap(), expected, "{:?}", expr);
+        }
+    }
+
+    #[test]
+    fn test_operators() {
+        let cases = [
+            ("//li = 2", true),
+            ("//li[1] != 2", true),
+            ("//li[3] > 3", false),
+            ("//li[3] >= 3", true),
+            ("2 < //li[3]", true),
+            ("//li = //p", false),
+            ("//li = //li", true),
+            ("//nothing = false()", true),
+            ("//li = true()", true),
+            ("'1.0' = 1", true),
+            ("'a' != 'a'", false),
+            ("1 = 1 and 2 > 1 or false()", true),
+            ("7 mod 3 = 1", true),
+            ("-7 mod 3 = -1", true),
+            ("- - 2 = 2", true),
+            ("(1 + 2) * 3 = 9", true),
+            ("3 div 2 = 1.5", true),
+        ];
+        for (expr, expected) in cases {
+            let value = evaluate(expr).1.unwrap();
+            assert_eq!(value, XValue::Boolean(expected), "{:?}", expr);
+        }
+    }
+
+    #[test]
+    fn test_deviations() {
+        // Where sxd-xpath departs from the specification, see `XPath`. The values the
+        // specification asks for are in the comments.
+        let cases = [
+            // true: some li is not 2
+            ("//li != 2", XValue::Boolean(false)),
+            // true: the third li is
+            ("//li >= 3", XValue::Boolean(false)),
+            // true: the third li is
+            ("2 < //li", XValue::Boolean(false)),
+            // NaN
+            ("number('1e3')", XValue::Number(1000.0)),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(evaluate(expr).1.unwrap(), expected, "{:?}", expr);
+        }
+        // Nothing: no position is 1.5
+        assert_eq!(labels("//li[1.5]"), vec!["li"]);
+        assert_eq!(labels("(//li)[1.5]/text()"), vec!["1"]);
+        // b as well, a descendant of the element of the attribute
+        assert_eq!(
+            labels("//@data-n/following::*"),
+            vec!["ul", "li", "li", "li", "footer"]
+        );
+    }
+
+    #[test]
+    fn test_copy_reused() {
+        let doc = Arc::new(Document::parse(PAGE));
+        let package = |doc: &Document| &**doc.sxd_copy().0.backing_cart() as *const Package;
+        let first = package(&doc);
+        let xpath = XPath::parse("count(//li)").unwrap();
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let doc = Arc::clone(&doc);
+                std::thread::spawn(move || {
+                    XPath::parse("count(//li)")
+                        .unwrap()
+                        .evaluate(&doc, Document::ROOT)
+                        .unwrap()
+                })
+            })
+            .collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), XValue::Number(3.0));
+        }
+        assert_eq!(
+            xpath.evaluate(&doc, Document::ROOT).unwrap(),
+            XValue::Number(3.0)
+        );
+        assert_eq!(package(&doc), first);
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            "//a]",
+            "//a[1",
+            "(//a",
+            "child::",
+            "bogus::a",
+            "//a/unknown()",
+            "'unfinished",
+            "1 ! 2",
+            "#",
+            "//a[@]",
+            "@",
+            "concat(1,",
+            "f(1 2)",
+            "a b",
+            "1 div",
+        ];
+        for expr in cases {
+            assert!(XPath::parse(expr).is_err(), "{:?}", expr);
+        }
+        let err = XPath::parse("bogus::a").unwrap_err().to_string();
+        assert!(err.starts_with("Invalid XPath \"bogus::a\": "), "{}", err);
+
+        let cases = [
+            "count(1)",
+            "'a'[1]",
+            "1/a",
+            "foo()",
+            "lang('en')",
+            "concat('a')",
+            "substring('a')",
+            "last(1)",
+            "$x",
+        ];
+        for expr in cases {
+            let err = evaluate(expr).1.unwrap_err().to_string();
+            assert!(err.starts_with("XPath evaluation failed: "), "{:?}", expr);
+        }
+    }
+}
@@ -267,3 +267,37 @@ async def test_client_impersonate_chrome131():
         == "90224459f8bf70b7d0a8797eb916dbc9"
     )
     assert json_data["tls"]["peetprint_hash"] == "7466733991096b3f4e6c0e79b0083559"
+
+
+@pytest.mark.asyncio
+@async_retry()
+async def test_client_concurrent_requests():
+    client = primp.AsyncClient()
+    urls = [f"https://httpbin.org/anything?n={i}" for i in range(10)]
+    responses = await asyncio.gather(*(client.get(url) for url in urls))
+    assert [r.status_code for r in responses] == [200] * 10
+    assert [r.json()["args"]["n"] for r in responses] == [str(i) for i in range(10)]
+
+
+@pytest.mark.asyncio
+@async_retry()
+async def test_client_map():
+    client = primp.AsyncClient()
+    urls = [f"https://httpbin.org/anything?n={i}" for i in range(10)]
+    responses = await client.map("GET", urls, concurrency=5)
+    assert [r.json()["args"]["n"] for r in responses] == [str(i) for i in range(10)]
+
+
+@pytest.mark.asyncio
+@async_retry()
+async def test_client_mixed_sync_async_requests():
+    # The async requests reuse the pooled connections opened by the sync ones
+    client = primp.Client()
+    for i in range(3):
+        response = client.get(f"https://httpbin.org/anything?sync={i}")
+        assert response.status_code == 200
+        response = await asyncio.wait_for(
+            client.arequest("GET", f"https://httpbin.org/anything?async={i}"), timeout=30
+        )
+        assert response.status_code == 200
+        assert response.json()["args"] == {"async": str(i)}
@@ -1,4 +1,12 @@
-from time import sleep
+import gzip
+import json
+import threading
+from base64 import urlsafe_b64encode
+from collections.abc import Mapping
+from decimal import Decimal
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+from time import monotonic, sleep
+from urllib.parse import urlencode
 
 import pytest
 
@@ -202,6 +210,31 @@ def test_client_post_json():
     assert json_data["json"] == data
 
 
+@retry()
+def test_client_post_content_stream(tmp_path):
+    client = primp.Client()
+
+    def chunks():
+        yield b"test "
+        yield bytearray(b"streamed ")
+        yield b"content"
+
+    response = client.post("https://httpbin.org/anything", content=chunks())
+    assert response.status_code == 200
+    json_data = response.json()
+    assert json_data["headers"]["Transfer-Encoding"] == "chunked"
+    assert json_data["data"] == "test streamed content"
+
+    path = tmp_path / "content.bin"
+    path.write_bytes(b"file content" * 10000)
+    with open(path, "rb") as f:
+        response = client.post("https://httpbin.org/anything", content=f)
+    assert response.json()["data"] == "file content" * 10000
+
+    with pytest.raises(Exception):
+        client.post("https://httpbin.org/anything", content="str content")
+
+
 @pytest.fixture(scope="session")
 def test_files(tmp_path_factory):
     tmp_path_factory.mktemp("data")
@@ -260,3 +293,635 @@ def test_client_impersonate_chrome131():
         == "90224459f8bf70b7d0a8797eb916dbc9"
     )
     assert json_data["tls"]["peetprint_hash"] == "7466733991096b3f4e6c0e79b0083559"
+
+
+@retry()
+def test_client_runtime():
+    assert primp.Client().runtime == "current_thread"
+    assert primp.Client().worker_threads is None
+    for mode in ("multi_thread", "dedicated"):
+        client = primp.Client(runtime=mode, worker_threads=2)
+        assert client.runtime == mode
+        assert client.worker_threads == 2
+        response = client.get("https://httpbin.org/anything")
+        assert response.status_code == 200
+    with pytest.raises(Exception):
+        primp.Client(runtime="invalid")
+
+
+@retry()
+def test_client_stream():
+    client = primp.Client()
+    response = client.get("https://httpbin.org/stream/5", stream=True)
+    assert response.status_code == 200
+    lines = list(response.iter_lines())
+    assert len(lines) == 5
+    assert all('"url": "https://httpbin.org/stream/5"' in line for line in lines)
+
+    response = client.get("https://httpbin.org/bytes/1000", stream=True)
+    chunks = list(response.iter_bytes(chunk_size=300))
+    assert [len(chunk) for chunk in chunks] == [300, 300, 300, 100]
+
+    with client.get("https://httpbin.org/anything", stream=True) as response:
+        text = "".join(response.iter_text())
+        assert '"method": "GET"' in text
+
+    response = client.get("https://httpbin.org/anything", stream=True)
+    assert response.read() == response.content
+    assert response.json()["method"] == "GET"
+
+    response = client.get("https://httpbin.org/anything", stream=True)
+    response.close()
+    with pytest.raises(Exception):
+        response.content
+
+
+@retry()
+def test_client_stream_other_thread():
+    # The response can be used by another thread while a chunk is awaited
+    client = primp.Client()
+    response = client.get("https://httpbin.org/drip?duration=2&numbytes=4&delay=0", stream=True)
+    errors = []
+
+    def use_response():
+        for _ in range(20):
+            try:
+                assert response.status_code == 200
+                assert response.headers["content-length"] == "4"
+            except Exception as e:
+                errors.append(e)
+            sleep(0.05)
+
+    thread = threading.Thread(target=use_response)
+    thread.start()
+    assert b"".join(response.iter_bytes()) == b"****"
+    thread.join()
+    assert errors == []
+
+
+@retry()
+def test_client_iter_events():
+    body = b': ping\n\ndata: {"n": 1}\nid: 1\n\nevent: done\ndata: a\ndata: b\nretry: 10\n\ndata: partial'
+    url = f"https://httpbin.org/base64/{urlsafe_b64encode(body).decode()}"
+    client = primp.Client()
+    events = list(client.get(url, stream=True).iter_events())
+    assert [(e.event, e.data, e.id, e.retry) for e in events] == [
+        ("message", '{"n": 1}', "1", None),
+        ("done", "a\nb", "1", 10),
+    ]
+    assert events[0].json() == {"n": 1}
+
+    # The reconnection is refused, the response is not a text/event-stream
+    events = list(client.get(url, stream=True).iter_events(reconnect=True))
+    assert len(events) == 2
+
+    with pytest.raises(Exception):
+        client.get(url).iter_events(reconnect=True)
+
+
+@retry()
+def test_client_headers():
+    client = primp.Client()
+    response = client.get("https://httpbin.org/response-headers?X-Multi=a&X-Multi=b")
+    headers = response.headers
+    assert headers["X-Multi"] == headers["x-multi"] == "a, b"
+    assert headers.get_all("X-MULTI") == ["a", "b"]
+    assert ("x-multi", "a") in headers.items() and ("x-multi", "b") in headers.items()
+    assert headers.get("missing") is None
+    assert headers.get("missing", "default") == "default"
+    assert "Content-Type" in headers
+    assert isinstance(headers, Mapping)
+    assert dict(headers)["x-multi"] == "a, b"
+    with pytest.raises(KeyError):
+        headers["missing"]
+
+
+@retry()
+def test_client_redirect_history():
+    client = primp.Client()
+    response = client.get("https://httpbin.org/redirect/3")
+    assert response.url == "https://httpbin.org/get"
+    assert [r.status_code for r in response.history] == [302, 302, 302]
+    assert response.history[0].url == "https://httpbin.org/redirect/3"
+    assert response.history[0].headers["location"] == "/relative-redirect/2"
+
+    response = client.get("https://httpbin.org/cookies/set?k1=v1&k2=v2")
+    assert response.json()["cookies"] == {"k1": "v1", "k2": "v2"}
+    assert len(response.history) == 1
+    assert sorted(c.split(";")[0] for c in response.history[0].set_cookies) == ["k1=v1", "k2=v2"]
+
+    # Cookies of the request are kept on same-host redirects, next to the ones they set
+    response = client.get("https://httpbin.org/cookies/set?k3=v3", cookies={"explicit": "x"})
+    assert response.json()["cookies"] == {"explicit": "x", "k1": "v1", "k2": "v2", "k3": "v3"}
+
+    # 303 switches to GET
+    response = client.post(
+        "https://httpbin.org/redirect-to",
+        params={"url": "/get", "status_code": "303"},
+        content=b"data",
+    )
+    assert response.history[0].method == "POST"
+    assert response.status_code == 200
+
+    # 307 can't resend a streamed body
+    with pytest.raises(primp.RequestError, match="streamed request body"):
+        client.post(
+            "https://httpbin.org/redirect-to",
+            params={"url": "/post", "status_code": "307"},
+            content=iter([b"data"]),
+        )
+
+    response = primp.Client(follow_redirects=False).get("https://httpbin.org/redirect/1")
+    assert response.status_code == 302
+    assert response.history == []
+
+
+@retry()
+def test_client_protocol_details():
+    response = primp.Client(impersonate="chrome_131").get("https://httpbin.org/anything")
+    assert response.http_version in ("HTTP/1.1", "HTTP/2")
+    assert response.remote_addr.endswith(":443")
+
+    response = primp.Client().get("http://httpbin.org/anything")
+    assert response.http_version == "HTTP/1.1"
+    assert response.remote_addr.endswith(":80")
+
+
+@retry()
+def test_client_timings():
+    client = primp.Client()
+    response = client.get("https://httpbin.org/delay/1")
+    timings = response.timings
+    assert timings.dns is not None and timings.connect is not None
+    # Not measured separately from connect
+    assert timings.tls is None
+    assert timings.ttfb >= 1
+    assert timings.download is not None
+    assert response.elapsed == timings.total == pytest.approx(timings.ttfb + timings.download)
+
+    # The pooled connection is reused
+    response = client.get("https://httpbin.org/anything")
+    assert response.timings.dns is None and response.timings.connect is None
+
+    response = client.get("https://httpbin.org/anything", stream=True)
+    assert response.timings.download is None
+    assert response.elapsed == response.timings.ttfb
+
+
+@retry()
+def test_client_download(tmp_path):
+    client = primp.Client()
+    path = tmp_path / "data.bin"
+    result = client.download("https://httpbin.org/range/1000", str(path))
+    assert result.bytes_written == 1000
+    assert result.resumed is False
+    assert result.url == "https://httpbin.org/range/1000"
+    data = path.read_bytes()
+    assert len(data) == 1000
+
+    # Resume a partial file
+    path.write_bytes(data[:400])
+    result = client.download("https://httpbin.org/range/1000", str(path))
+    assert result.bytes_written == 600
+    assert result.resumed is True
+    assert path.read_bytes() == data
+
+    # Error statuses raise HTTPStatusError, without a response
+    with pytest.raises(primp.HTTPStatusError) as exc_info:
+        client.download("https://httpbin.org/status/404", str(tmp_path / "missing.bin"))
+    assert exc_info.value.status_code == 404
+    assert exc_info.value.response is None
+    assert exc_info.value.url == "https://httpbin.org/status/404"
+    assert not (tmp_path / "missing.bin").exists()
+
+    # Overwrite instead of resuming
+    result = client.download("https://httpbin.org/range/1000", str(path), resume=False)
+    assert result.bytes_written == 1000
+    assert result.resumed is False
+    assert path.read_bytes() == data
+
+
+class GzipRangeHandler(BaseHTTPRequestHandler):
+    """Serves `body` gzip-compressed to clients accepting it, else byte ranges of it.
+    The first `failures` requests get a 503."""
+
+    body = bytes(range(256)) * 40
+    accept_encodings = []
+    failures = 0
+
+    def do_GET(self):
+        accept_encoding = self.headers.get("Accept-Encoding", "")
+        self.accept_encodings.append(accept_encoding)
+        if GzipRangeHandler.failures > 0:
+            GzipRangeHandler.failures -= 1
+            self.send_response(503)
+            self.send_header("Content-Length", "0")
+            self.end_headers()
+            return
+        range_header = self.headers.get("Range")
+        if "gzip" in accept_encoding:
+            payload = gzip.compress(self.body)
+            self.send_response(200)
+            self.send_header("Content-Encoding", "gzip")
+        elif range_header:
+            start = int(range_header[len("bytes="):].rstrip("-"))
+            payload = self.body[start:]
+            self.send_response(206)
+            self.send_header("Content-Range", f"bytes {start}-{len(self.body) - 1}/{len(self.body)}")
+        else:
+            payload = self.body
+            self.send_response(200)
+        self.send_header("Content-Length", str(len(payload)))
+        self.end_headers()
+        self.wfile.write(payload)
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def gzip_server():
+    server = ThreadingHTTPServer(("127.0.0.1", 0), GzipRangeHandler)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    yield f"http://127.0.0.1:{server.server_port}/data.bin"
+    server.shutdown()
+
+
+def test_client_download_identity(tmp_path, gzip_server):
+    client = primp.Client()
+    body = GzipRangeHandler.body
+    path = tmp_path / "data.bin"
+    path.write_bytes(body[:1000])
+    result = client.download(gzip_server, str(path))
+    assert GzipRangeHandler.accept_encodings[-1] == "identity"
+    assert result.resumed is True
+    assert result.bytes_written == len(body) - 1000
+    assert path.read_bytes() == body
+
+    # Other requests still get the body compressed, and decompress it
+    assert client.get(gzip_server).content == body
+    assert "gzip" in GzipRangeHandler.accept_encodings[-1]
+
+    # Downloads are subject to max_content_length, but not to max_decompressed_size
+    path.write_bytes(b"")
+    with pytest.raises(primp.ResponseTooLarge):
+        primp.Client(max_content_length=1000).download(gzip_server, str(path), resume=False)
+    primp.Client(max_decompressed_size=1000).download(gzip_server, str(path), resume=False)
+    assert path.read_bytes() == body
+
+    # Downloads are retried like GET requests
+    GzipRangeHandler.failures = 2
+    requests = len(GzipRangeHandler.accept_encodings)
+    client = primp.Client(retries=primp.Retry(backoff_base=0, jitter=0))
+    result = client.download(gzip_server, str(path), resume=False)
+    assert result.bytes_written == len(body)
+    assert len(GzipRangeHandler.accept_encodings) == requests + 3
+
+    # A resumed download counts the partial file against max_content_length
+    path.write_bytes(body[:1000])
+    with pytest.raises(primp.ResponseTooLarge):
+        primp.Client(max_content_length=len(body) - 1).download(gzip_server, str(path))
+    assert path.read_bytes() == body[:1000]
+
+
+@retry()
+def test_client_request_many():
+    client = primp.Client()
+    responses = client.request_many(
+        [
+            {"method": "GET", "url": "https://httpbin.org/anything", "params": {"n": "0"}},
+            {"method": "POST", "url": "https://httpbin.org/anything", "json": {"n": 1}},
+            {"url": "http://invalid.invalid/"},
+        ],
+        concurrency=2,
+    )
+    assert len(responses) == 3
+    assert responses[0].json()["args"] == {"n": "0"}
+    assert responses[1].json()["json"] == {"n": 1}
+    assert isinstance(responses[2], Exception)
+
+    urls = [f"https://httpbin.org/anything?n={i}" for i in range(10)]
+    responses = client.map("GET", urls, headers={"X-Test": "test"})
+    assert [r.json()["args"]["n"] for r in responses] == [str(i) for i in range(10)]
+    assert all(r.json()["headers"]["X-Test"] == "test" for r in responses)
+
+    with pytest.raises(Exception):
+        client.request_many([{"url": "https://httpbin.org/anything"}], concurrency=0)
+
+
+@retry()
+def test_client_exceptions():
+    client = primp.Client()
+    with pytest.raises(primp.TimeoutError) as exc_info:
+        client.get("https://httpbin.org/delay/3", timeout=1)
+    assert exc_info.value.url == "https://httpbin.org/delay/3"
+    assert exc_info.value.method == "GET"
+    assert isinstance(exc_info.value, primp.RequestError)
+    assert isinstance(exc_info.value, TimeoutError)
+
+    with pytest.raises(primp.ConnectError) as exc_info:
+        client.post("https://invalid.invalid/")
+    assert exc_info.value.method == "POST"
+
+    # Words of the URL don't make a connect error a TLS or proxy error
+    for url in ["https://sslvpn.invalid/", "https://proxy.invalid/", "http://127.0.0.1:1/api/proxy/ssl"]:
+        with pytest.raises(primp.ConnectError):
+            client.get(url)
+
+    with pytest.raises(primp.InvalidURL):
+        client.get("not a url")
+
+    with pytest.raises(primp.TooManyRedirects):
+        primp.Client(max_redirects=2).get("https://httpbin.org/redirect/5")
+
+
+@retry()
+def test_client_raise_for_status():
+    client = primp.Client()
+    response = client.get("https://httpbin.org/status/404")
+    assert response.status_code == 404
+    assert response.reason_phrase == "Not Found"
+    assert response.is_client_error
+    assert not response.is_success
+    assert not response.is_server_error
+    with pytest.raises(primp.HTTPStatusError) as exc_info:
+        response.raise_for_status()
+    assert exc_info.value.response.status_code == exc_info.value.status_code == 404
+    assert exc_info.value.url == "https://httpbin.org/status/404"
+    assert exc_info.value.method == "GET"
+
+    response = client.get("https://httpbin.org/status/200")
+    assert response.is_success
+    response.raise_for_status()
+
+    client = primp.Client(raise_for_status=True)
+    assert client.raise_for_status is True
+    with pytest.raises(primp.HTTPStatusError) as exc_info:
+        client.get("https://httpbin.org/status/503")
+    assert exc_info.value.response.is_server_error
+    assert isinstance(exc_info.value, primp.RequestError)
+
+
+@retry()
+def test_client_retries():
+    policy = primp.Retry(max_attempts=3, backoff_base=0.2, jitter=0)
+    assert policy.status_codes == [429, 502, 503, 504]
+    client = primp.Client(retries=policy)
+    assert client.retries.max_attempts == 3
+
+    # 2 retries: 0.2 + 0.4 seconds of backoff
+    start = monotonic()
+    response = client.get("https://httpbin.org/status/503")
+    assert response.status_code == 503
+    assert monotonic() - start >= 0.6
+
+    # Non-idempotent methods and disabled retries are sent once
+    client = primp.Client(retries=primp.Retry(backoff_base=5, jitter=0))
+    start = monotonic()
+    client.post("https://httpbin.org/status/503")
+    client.get("https://httpbin.org/status/503", retries=0)
+    assert monotonic() - start < 5
+
+    client = primp.Client(retries=1)
+    assert client.retries.max_attempts == 2
+    with pytest.raises(Exception):
+        primp.Retry(max_attempts=0)
+
+
+@retry()
+def test_client_rate_limit():
+    client = primp.Client(rate_limit=4, max_connections_per_host=2)
+    assert client.rate_limit == 4
+    assert client.max_connections_per_host == 2
+
+    # 5 requests at 4 per second: the last one is sent 1 second after the first
+    start = monotonic()
+    responses = client.map("GET", ["https://httpbin.org/anything"] * 5)
+    assert all(r.status_code == 200 for r in responses)
+    assert monotonic() - start >= 1
+
+    # Each redirect hop waits for the limits of its host too
+    client = primp.Client(rate_limit=2)
+    start = monotonic()
+    response = client.get("https://httpbin.org/redirect/2")
+    assert len(response.history) == 2
+    assert monotonic() - start >= 1
+
+    with pytest.raises(Exception):
+        primp.Client(rate_limit=0)
+    with pytest.raises(Exception):
+        primp.Client(max_connections_per_host=0)
+
+
+@retry()
+def test_client_cookie_objects():
+    set_cookies = [
+        "sid=abc; Domain=httpbin.org; Path=/docs; Max-Age=3600; Secure; HttpOnly; SameSite=None; Partitioned",
+        "theme=dark; Expires=Wed, 21 Oct 2037 07:28:00 GMT",
+    ]
+    client = primp.Client()
+    query = urlencode([("Set-Cookie", c) for c in set_cookies])
+    response = client.get(f"https://httpbin.org/response-headers?{query}")
+    assert response.cookies == {"sid": "abc", "theme": "dark"}
+    sid, theme = response.cookie_objects
+    assert (sid.name, sid.value) == ("sid", "abc")
+    assert sid.domain == "httpbin.org"
+    assert sid.path == "/docs"
+    assert sid.max_age == 3600
+    assert sid.secure and sid.httponly and sid.partitioned
+    assert sid.samesite == "None"
+    assert theme.expires == 2139722880
+    assert theme.max_age is None and theme.path is None and theme.samesite is None
+    assert not theme.secure
+
+    response = client.get("https://httpbin.org/cookies/set?k1=v1")
+    assert [c.name for c in response.history[0].cookie_objects] == ["k1"]
+
+    # Cookie store of the client
+    cookies = {c.name: c for c in client.get_cookie_objects("https://httpbin.org/docs/page")}
+    assert sorted(cookies) == ["k1", "sid", "theme"]
+    assert (cookies["sid"].path, cookies["sid"].max_age) == ("/docs", 3600)
+    assert sorted(c.name for c in client.get_cookie_objects("https://httpbin.org/")) == ["k1", "theme"]
+    assert primp.Client(cookie_store=False).get_cookie_objects("https://httpbin.org/") == []
+
+    with pytest.raises(Exception):
+        client.cookies = {"bad": "new\nline"}
+
+
+@retry()
+def test_client_json_options():
+    document = b'{"big": 123456789012345678901234567890, "price": 0.10, "text": "caf\\u00e9"}'
+    url = f"https://httpbin.org/base64/{urlsafe_b64encode(document).decode()}"
+    response = primp.Client().get(url)
+    assert response.json() == {"big": 1.2345678901234568e29, "price": 0.1, "text": "café"}
+    assert response.json(big_int=True, decimal=True) == {
+        "big": 123456789012345678901234567890,
+        "price": Decimal("0.10"),
+        "text": "café",
+    }
+    assert response.json(release_gil=True) == response.json()
+
+    response = primp.Client().get(f"https://httpbin.org/base64/{urlsafe_b64encode(b'[NaN, 1]').decode()}")
+    with pytest.raises(Exception):
+        response.json()
+    assert response.json(strict=False)[1] == 1
+
+    # Lone surrogates are kept outside of strict mode, like json.loads does
+    document = b'{"\\ud800k": ["a\\udc00b", "\\ud83d\\ude00"]}'
+    response = primp.Client().get(f"https://httpbin.org/base64/{urlsafe_b64encode(document).decode()}")
+    with pytest.raises(Exception):
+        response.json()
+    assert response.json(strict=False) == json.loads(document)
+    assert response.json(strict=False, release_gil=True) == {"\ud800k": ["a\udc00b", "\U0001f600"]}
+
+
+@retry()
+def test_client_select_xpath():
+    response = primp.Client().get("https://httpbin.org/links/3/1")
+    links = response.select("a[href]")
+    assert [a.text for a in links] == ["0", "2"]
+    assert links[0].tag == "a"
+    assert links[0].attrs == {"href": "/links/3/0"}
+    assert links[1].get("href") == "/links/3/2"
+    assert links[1].html == '<a href="/links/3/2">2</a>'
+    assert response.select_one("title").text == "Links"
+    assert response.select_one("table") is None
+    assert response.select_one("body").select_one("a").text == "0"
+
+    assert response.xpath("//a/@href") == ["/links/3/0", "/links/3/2"]
+    assert response.xpath("count(//a)") == 2.0
+    assert response.xpath("string(//title)") == "Links"
+    assert [a.text for a in response.xpath("//a[last()]")] == ["2"]
+    with pytest.raises(Exception):
+        response.select("a[")
+
+
+@retry()
+def test_client_links():
+    response = primp.Client().get("https://httpbin.org/links/3/1")
+    links = response.links()
+    assert [link.url for link in links] == ["https://httpbin.org/links/3/0", "https://httpbin.org/links/3/2"]
+    assert links[0].text == "0"
+    assert links[0].tag == "a"
+    assert links[0].rel == []
+    assert links[0].attrs == {"href": "/links/3/0"}
+    assert len(response.links(same_host=True)) == 2
+    assert response.images() == []
+
+    response = primp.Client().get("https://httpbin.org/html")
+    assert response.assets() == []
+
+
+@retry()
+def test_client_metadata():
+    response = primp.Client().get("https://httpbin.org/links/3/1")
+    metadata = response.metadata
+    assert metadata.title == "Links"
+    assert metadata.description is None
+    assert metadata.canonical is None
+    assert metadata.opengraph == {}
+    assert metadata.twitter == {}
+    assert metadata.json_ld == []
+
+
+@retry()
+def test_client_article():
+    response = primp.Client().get("https://httpbin.org/html")
+    article = response.article
+    assert article.title is None
+    assert "Availing himself of the mild, summer-cool weather" in article.html
+    assert "Availing himself" in article.text
+    assert "Availing himself" in response.text_main
+    assert "<h1>" not in article.text
+    assert article.markdown == article.to_markdown()
+    assert article.text == article.to_text()
+    assert all(len(line) <= 40 for line in article.to_text(width=40).splitlines())
+    assert all(len(line) <= 40 for line in article.to_markdown(width=40).splitlines())
+
+
+@retry()
+def test_client_apparent_encoding():
+    text = "Съешь же ещё этих мягких французских булок, да выпей чаю. Широкая электрификация южных губерний."
+    url = f"https://httpbin.org/base64/{urlsafe_b64encode(text.encode('windows-1251')).decode()}"
+    response = primp.Client().get(url)
+    assert response.encoding == "utf-8"  # declared by the Content-Type header
+    assert response.apparent_encoding == "windows-1251"
+    response.encoding = response.apparent_encoding
+    assert response.text == text
+
+
+@retry()
+def test_client_to_markdown():
+    html = '<p>Привет, <a href="https://example.com/">мир</a> <img src="a.png" alt="логотип"></p>'
+    url = f"https://httpbin.org/base64/{urlsafe_b64encode(html.encode('windows-1251')).decode()}"
+    response = primp.Client().get(url)
+    response.encoding = "windows-1251"
+    assert "Привет, [мир][1] [логотип]" in response.text_markdown
+    assert "[1]: https://example.com/" in response.text_markdown
+    assert "Привет, мир логотип" in response.text_plain
+    markdown = response.to_markdown(width=40, link_footnotes=False, image_alt=False)
+    assert markdown.strip() == "Привет, [мир](https://example.com/)"
+    assert response.to_text() == response.text_plain
+    assert response.to_text(rich=True) == response.text_rich
+    assert all(len(line) <= 10 for line in response.to_text(width=10).splitlines())
+    with pytest.raises(Exception, match="Invalid tables"):
+        response.to_text(tables="grid")
+
+
+@retry()
+def test_client_decompress():
+    client = primp.Client()
+    response = client.get("https://httpbin.org/gzip")
+    assert response.json()["gzipped"] is True
+    assert "content-encoding" not in response.headers
+    response = client.get("https://httpbin.org/gzip", decompress=False)
+    assert response.headers["content-encoding"] == "gzip"
+    assert response.content[:2] == b"\x1f\x8b"
+    assert b'"gzipped": true' in gzip.decompress(response.content)
+
+    client = primp.Client(decompress=False)
+    assert client.decompress is False
+    assert client.get("https://httpbin.org/gzip").content[:2] == b"\x1f\x8b"
+    assert client.get("https://httpbin.org/gzip", decompress=True).json()["gzipped"] is True
+
+
+@retry()
+def test_client_accept_encoding():
+    client = primp.Client(accept_encoding=["br", "gzip"])
+    assert client.accept_encoding == ["br", "gzip"]
+    response = client.get("https://httpbin.org/headers")
+    assert response.json()["headers"]["Accept-Encoding"] == "br, gzip"
+    response = client.get("https://httpbin.org/headers", headers={"Accept-Encoding": "zstd"})
+    assert response.json()["headers"]["Accept-Encoding"] == "zstd"
+    response = primp.Client(accept_encoding=[]).get("https://httpbin.org/headers")
+    assert response.json()["headers"]["Accept-Encoding"] == "identity"
+    with pytest.raises(Exception, match="Invalid accept_encoding"):
+        primp.Client(accept_encoding=["compress"])
+
+
+@retry()
+def test_client_max_content_length():
+    client = primp.Client(max_content_length=100)
+    assert client.max_content_length == 100
+    # Rejected upfront from its Content-Length
+    with pytest.raises(primp.ResponseTooLarge, match="max_content_length"):
+        client.get("https://httpbin.org/bytes/1000")
+    response = client.get("https://httpbin.org/bytes/1000", max_content_length=1000)
+    assert len(response.content) == 1000
+    # Without Content-Length, reading stops at the limit
+    response = client.get(
+        "https://httpbin.org/stream-bytes/2000?chunk_size=100", max_content_length=500, stream=True
+    )
+    with pytest.raises(primp.ResponseTooLarge):
+        response.read()
+
+
+@retry()
+def test_client_max_decompressed_size():
+    client = primp.Client(max_decompressed_size=10)
+    with pytest.raises(primp.ResponseTooLarge, match="max_decompressed_size"):
+        client.get("https://httpbin.org/gzip")
+    response = client.get("https://httpbin.org/gzip", decompress=False)
+    assert response.headers["content-encoding"] == "gzip"
+    response = client.get("https://httpbin.org/gzip", max_decompressed_size=100_000)
+    assert response.json()["gzipped"] is True